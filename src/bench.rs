@@ -0,0 +1,184 @@
+// time the phases of proving a circuit: transpile, setup polynomials, vk, and then proof and verification
+// for each SRS form and transcript, over several repetitions
+use crate::bellman_ce::pairing::bn256::Bn256;
+use crate::circom_circuit::CircomCircuit;
+use crate::transpile::transpile_with_gates_count;
+use crate::{plonk, reader};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub const SRS_FORMS: [&str; 2] = ["monomial", "lagrange"];
+pub const TRANSCRIPTS: [&str; 2] = ["keccak", "rescue"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PhaseTimings {
+    pub name: String,
+    pub runs_ms: Vec<f64>,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+    // peak resident set size of the process during the phase in KiB (what it already held included),
+    // the largest over the runs; only known on linux
+    pub peak_rss_kb: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchResult {
+    pub num_gates: usize,
+    pub domain_size: usize,
+    pub repetitions: usize,
+    pub phases: Vec<PhaseTimings>,
+    // the prove phases of the requested srs forms and transcripts which the prover doesn't support
+    pub skipped: Vec<String>,
+}
+
+pub struct BenchConfig {
+    pub srs_monomial_form: String,
+    // a lagrange_form SRS file, computed from the monomial_form one (and timed) if missing
+    pub srs_lagrange_form: Option<String>,
+    pub srs_forms: Vec<String>,
+    pub transcripts: Vec<String>,
+    pub repetitions: usize,
+}
+
+#[derive(Default)]
+struct Timings {
+    phases: Vec<(String, Vec<f64>, Option<u64>)>,
+}
+
+impl Timings {
+    fn time<T, F: FnOnce() -> T>(&mut self, name: &str, f: F) -> T {
+        let reset = reset_peak_rss();
+        let start = Instant::now();
+        let result = f();
+        let ms = start.elapsed().as_secs_f64() * 1000.0;
+        let rss = if reset { peak_rss_kb() } else { None };
+        log::info!("{}: {:.1} ms", name, ms);
+        match self.phases.iter_mut().find(|(n, _, _)| n == name) {
+            Some((_, runs, peak)) => {
+                runs.push(ms);
+                *peak = (*peak).max(rss);
+            }
+            None => self.phases.push((name.to_owned(), vec![ms], rss)),
+        }
+        result
+    }
+
+    fn into_phases(self) -> Vec<PhaseTimings> {
+        self.phases
+            .into_iter()
+            .map(|(name, runs_ms, peak_rss_kb)| PhaseTimings {
+                mean_ms: runs_ms.iter().sum::<f64>() / runs_ms.len() as f64,
+                min_ms: runs_ms.iter().cloned().fold(f64::INFINITY, f64::min),
+                max_ms: runs_ms.iter().cloned().fold(0.0, f64::max),
+                name,
+                runs_ms,
+                peak_rss_kb,
+            })
+            .collect()
+    }
+}
+
+// reset the VmHWM of the process to its current rss, so that it is the peak of what runs next
+fn reset_peak_rss() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+// VmHWM of /proc/self/status
+fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+// the lagrange_form prover only supports the keccak transcript
+fn is_supported(srs_form: &str, transcript: &str) -> bool {
+    srs_form == "monomial" || transcript == "keccak"
+}
+
+pub fn bench(circuit: CircomCircuit<Bn256>, config: &BenchConfig) -> Result<BenchResult, anyhow::Error> {
+    anyhow::ensure!(config.repetitions > 0, "repetitions must be positive");
+    for srs_form in &config.srs_forms {
+        anyhow::ensure!(SRS_FORMS.contains(&srs_form.as_str()), "unknown srs form {}", srs_form);
+    }
+    for transcript in &config.transcripts {
+        anyhow::ensure!(TRANSCRIPTS.contains(&transcript.as_str()), "unknown transcript {}", transcript);
+    }
+
+    let skipped = config
+        .srs_forms
+        .iter()
+        .flat_map(|srs_form| config.transcripts.iter().map(move |transcript| (srs_form, transcript)))
+        .filter(|(srs_form, transcript)| !is_supported(srs_form, transcript))
+        .map(|(srs_form, transcript)| format!("prove_{}_{}", srs_form, transcript))
+        .collect();
+
+    let mut timings = Timings::default();
+    let mut num_gates = 0;
+    let mut domain_size = 0;
+    for repetition in 0..config.repetitions {
+        log::info!("repetition {}/{}", repetition + 1, config.repetitions);
+        // loading the srs is not timed
+        let key_monomial_form = reader::load_key_monomial_form(&config.srs_monomial_form);
+        let (gates_count, hints) = timings.time("transpile", || transpile_with_gates_count(circuit.clone()))?;
+        num_gates = gates_count;
+        let mut setup = timings.time("setup", || {
+            plonk::SetupForProver::prepare_setup_for_prover_with_hints(circuit.clone(), hints, key_monomial_form, None)
+        })?;
+        domain_size = setup.domain_size();
+        setup
+            .validate_witness(circuit.clone())
+            .map_err(|e| anyhow::format_err!("witness is not satisfied: {:?}", e))?;
+        let vk = timings.time("vk", || setup.make_verification_key())?;
+
+        for srs_form in &config.srs_forms {
+            let key_lagrange_form = match (srs_form.as_str(), &config.srs_lagrange_form) {
+                ("lagrange", Some(file)) => reader::maybe_load_key_lagrange_form(Some(file.clone())),
+                ("lagrange", None) => Some(timings.time("lagrange_srs", || setup.get_srs_lagrange_form_from_monomial_form())),
+                _ => None,
+            };
+            setup.set_key_lagrange_form(key_lagrange_form.map(Arc::new));
+            for transcript in config.transcripts.iter().filter(|t| is_supported(srs_form, t)) {
+                let proof = timings.time(&format!("prove_{}_{}", srs_form, transcript), || {
+                    setup.prove(circuit.clone(), transcript)
+                })?;
+                let valid = timings.time(&format!("verify_{}", transcript), || plonk::verify(&vk, &proof, transcript))?;
+                anyhow::ensure!(valid, "the {} {} proof is invalid", srs_form, transcript);
+            }
+        }
+    }
+
+    Ok(BenchResult {
+        num_gates,
+        domain_size,
+        repetitions: config.repetitions,
+        phases: timings.into_phases(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings() {
+        let mut timings = Timings::default();
+        for _ in 0..2 {
+            assert_eq!(timings.time("a", || 1), 1);
+            timings.time("b", || ());
+        }
+        let phases = timings.into_phases();
+        assert_eq!(phases.len(), 2);
+        assert_eq!(phases[0].name, "a");
+        assert_eq!(phases[0].runs_ms.len(), 2);
+        assert!(phases[0].min_ms <= phases[0].mean_ms && phases[0].mean_ms <= phases[0].max_ms);
+    }
+
+    #[test]
+    fn test_is_supported() {
+        assert!(is_supported("monomial", "rescue"));
+        assert!(is_supported("lagrange", "keccak"));
+        assert!(!is_supported("lagrange", "rescue"));
+    }
+}
@@ -6,20 +6,32 @@ extern crate plonkit;
 
 use clap::Clap;
 use std::fs::File;
+use std::io::BufWriter;
 use std::path::Path;
 use std::str;
 
 use plonkit::bellman_ce::pairing::bn256::Bn256;
+use plonkit::bellman_ce::plonk::{
+    better_cs::cs::PlonkCsWidth4WithNextStepParams,
+    better_cs::keys::{Proof, VerificationKey},
+};
 
 use plonkit::circom_circuit::CircomCircuit;
+use plonkit::gate_circuit::{is_gate_circuit_file, GateCircuit};
+use plonkit::manifest::Manifest;
+use plonkit::output;
 use plonkit::plonk;
 use plonkit::reader;
 use plonkit::recursive;
+use plonkit::rust_verifier;
 
 /// A zkSNARK toolkit to work with circom zkSNARKs DSL in plonk proof system
 #[derive(Clap)]
 #[clap(version = "0.1.6")]
 struct Opts {
+    /// Print the result as a json object on stdout
+    #[clap(long = "json", global = true)]
+    json: bool,
     #[clap(subcommand)]
     command: SubCommand,
 }
@@ -38,18 +50,40 @@ enum SubCommand {
     Verify(VerifyOpts),
     /// Generate verifier smart contract
     GenerateVerifier(GenerateVerifierOpts),
+    /// Generate a standalone rust verifier crate
+    GenerateRustVerifier(GenerateRustVerifierOpts),
     /// Generate recursive verifier smart contract
     GenerateRecursiveVerifier(GenerateRecursiveVerifierOpts),
     /// Export verifying key
     ExportVerificationKey(ExportVerificationKeyOpts),
     /// Export Recursive verifying key
     ExportRecursiveVerificationKey(ExportRecursiveVerificationKeyOpts),
+    /// Export Recursive proving key, the setup of the recursion circuit
+    ExportRecursiveProvingKey(ExportRecursiveProvingKeyOpts),
     /// Aggregate multiple proofs
     RecursiveProve(RecursiveProveOpts),
     /// Verify recursive proof
     RecursiveVerify(RecursiveVerifyOpts),
     /// Check proofs aggregation
     CheckAggregation(CheckAggregationOpts),
+    /// Generate calldata for a verifier smart contract
+    Calldata(CalldataOpts),
+    /// Verify a proof with a verifier smart contract in an embedded EVM
+    #[cfg(feature = "evm")]
+    VerifyEvm(VerifyEvmOpts),
+    /// Estimate the on-chain verification gas of a verifier smart contract
+    EstimateGas(EstimateGasOpts),
+    /// Serve proving requests over HTTP, keeping the circuit setups in memory
+    #[cfg(feature = "serve")]
+    Serve(ServeOpts),
+    /// Queue prove and recursive-prove jobs, and run them with crash recovery
+    Queue(QueueOpts),
+    /// Run setup, export-verification-key, prove, verify and generate-verifier with the plonkit.toml manifest
+    Pipeline(PipelineOpts),
+    /// Benchmark the proving phases of a circuit
+    Bench(BenchOpts),
+    /// Optimise a circuit: substitute out linear constraints, merge duplicate ones and drop unused wires
+    OptimiseR1cs(OptimiseR1csOpts),
 }
 
 /// A subcommand for analysing the circuit and outputting some stats
@@ -58,9 +92,18 @@ struct AnalyseOpts {
     /// Circuit R1CS or JSON file [default: circuit.r1cs|circuit.json]
     #[clap(short = "c", long = "circuit")]
     circuit: Option<String>,
+    /// Circom symbols file, to break the gates down by circuit component
+    #[clap(short = "s", long = "sym")]
+    sym: Option<String>,
     /// Output file
     #[clap(short = "o", long = "output", default_value = "analyse.json")]
     output: String,
+    /// Output file of the gates by component as folded stacks, for flamegraph.pl or inferno
+    #[clap(short = "f", long = "folded")]
+    folded: Option<String>,
+    /// Estimate the proving time too, from a multiexp and FFT timed on this machine (takes a few seconds)
+    #[clap(long = "estimate-time")]
+    estimate_time: bool,
 }
 
 /// A subcommand for locally trusted setting up Plonk universal srs in monomial form
@@ -101,12 +144,15 @@ struct ProveOpts {
     /// Source file for Plonk universal setup srs in lagrange form
     #[clap(short = "l", long = "srs_lagrange_form")]
     srs_lagrange_form: Option<String>,
-    /// Circuit R1CS or JSON file [default: circuit.r1cs|circuit.json]
+    /// Circuit R1CS, JSON or native PLONK .plonk.json file [default: circuit.r1cs|circuit.json]
     #[clap(short = "c", long = "circuit")]
     circuit: Option<String>,
     /// Witness BIN or JSON file
     #[clap(short = "w", long = "witness", default_value = "witness.wtns")]
     witness: String,
+    /// Wire mapping file of a circuit from optimise-r1cs, to prove it with the witness of the original circuit
+    #[clap(long = "wire_mapping")]
+    wire_mapping: Option<String>,
     /// Output file for proof BIN
     #[clap(short = "p", long = "proof", default_value = "proof.bin")]
     proof: String,
@@ -151,15 +197,40 @@ struct GenerateVerifierOpts {
     overwrite: bool,
 }
 
+/// A subcommand for generating a standalone rust verifier crate
+#[derive(Clap)]
+struct GenerateRustVerifierOpts {
+    /// Verification key file
+    #[clap(short = "v", long = "verification_key", default_value = "vk.bin")]
+    vk: String,
+    /// Output directory for the crate
+    #[clap(short = "o", long = "output", default_value = "verifier")]
+    output: String,
+    /// Name of the crate
+    #[clap(short = "n", long = "name", default_value = "plonk_verifier")]
+    name: String,
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+}
+
 /// A subcommand for generating a Solidity recursive verifier smart contract
 #[derive(Clap)]
 struct GenerateRecursiveVerifierOpts {
     /// Original individual verification key file
     #[clap(short = "o", long = "old_vk", default_value = "vk.bin")]
     old_vk: String,
+    /// Original individual verification key list text file, used instead of old_vk for proofs of different circuits
+    #[clap(short = "l", long = "old_vk_list")]
+    old_vk_list: Option<String>,
+    /// Depth of the vk tree, which holds up to 2^depth vks
+    #[clap(short = "d", long = "vk_tree_depth", default_value = "7")]
+    vk_tree_depth: usize,
     /// Aggregated verification key file
     #[clap(short = "n", long = "new_vk", default_value = "recursive_vk.bin")]
     new_vk: String,
+    /// Skip checking the parameters against the metadata of the recursive vk, even when it has one
+    #[clap(long = "skip-vk-metadata")]
+    skip_vk_metadata: bool,
     /// Num of inputs
     #[clap(short = "i", long = "num_inputs")]
     num_inputs: usize,
@@ -179,9 +250,12 @@ struct ExportVerificationKeyOpts {
     /// Source file for Plonk universal setup srs in monomial form
     #[clap(short = "m", long = "srs_monomial_form")]
     srs_monomial_form: String,
-    /// Circuit R1CS or JSON file [default: circuit.r1cs|circuit.json]
+    /// Circuit R1CS, JSON or native PLONK .plonk.json file [default: circuit.r1cs|circuit.json]
     #[clap(short = "c", long = "circuit")]
     circuit: Option<String>,
+    /// Export the vk of the dummy circuit with this num of inputs instead, for `recursive-prove --dummy-proofs`
+    #[clap(long = "dummy-inputs")]
+    dummy_inputs: Option<usize>,
     /// Output verifying key file
     #[clap(short = "v", long = "vk", default_value = "vk.bin")]
     vk: String,
@@ -189,6 +263,25 @@ struct ExportVerificationKeyOpts {
     overwrite: bool,
 }
 
+/// A subcommand for exporting recursive proving keys
+#[derive(Clap)]
+struct ExportRecursiveProvingKeyOpts {
+    /// Num of proofs to check
+    #[clap(short = "c", long = "num_proofs_to_check")]
+    num_proofs_to_check: usize,
+    /// Num of inputs
+    #[clap(short = "i", long = "num_inputs")]
+    num_inputs: usize,
+    /// Depth of the vk tree, which holds up to 2^depth vks
+    #[clap(short = "d", long = "vk_tree_depth", default_value = "7")]
+    vk_tree_depth: usize,
+    /// Output proving key file
+    #[clap(short = "p", long = "pk", default_value = "recursive_pk.bin")]
+    pk: String,
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+}
+
 /// A subcommand for exporting recursive verifying keys
 #[derive(Clap)]
 struct ExportRecursiveVerificationKeyOpts {
@@ -201,9 +294,18 @@ struct ExportRecursiveVerificationKeyOpts {
     /// Source file for a BIG Plonk universal setup srs in monomial form
     #[clap(short = "m", long = "srs_monomial_form")]
     srs_monomial_form: String,
-    /// Output verifying key file
+    /// Output verifying key file, its metadata is saved next to it as a json file
     #[clap(short = "v", long = "vk", default_value = "recursive_vk.bin")]
     vk: String,
+    /// Output verifying key json file
+    #[clap(short = "j", long = "vkjson")]
+    vkjson: Option<String>,
+    /// Recursive proving key file, to export the vk from instead of recomputing the setup
+    #[clap(short = "p", long = "pk")]
+    pk: Option<String>,
+    /// Depth of the vk tree, which holds up to 2^depth vks
+    #[clap(short = "d", long = "vk_tree_depth", default_value = "7")]
+    vk_tree_depth: usize,
     #[clap(long = "overwrite")]
     overwrite: bool,
 }
@@ -214,12 +316,39 @@ struct RecursiveProveOpts {
     /// Source file for a BIG Plonk universal setup srs in monomial form
     #[clap(short = "m", long = "srs_monomial_form")]
     srs_monomial_form: String,
-    /// Old proof file list text file
+    /// Old proof file list text file, each line is a proof file optionally followed by the index of its vk
     #[clap(short = "f", long = "old_proof_list")]
     old_proof_list: String,
     /// Old vk
     #[clap(short = "v", long = "old_vk", default_value = "vk.bin")]
     old_vk: String,
+    /// Old vk list text file, used instead of old_vk when aggregating proofs of different circuits
+    #[clap(short = "l", long = "old_vk_list")]
+    old_vk_list: Option<String>,
+    /// Depth of the vk tree, which holds up to 2^depth vks
+    #[clap(short = "d", long = "vk_tree_depth", default_value = "7")]
+    vk_tree_depth: usize,
+    /// Recursive proving key file, to load instead of recomputing the setup
+    #[clap(short = "p", long = "pk")]
+    pk: Option<String>,
+    /// Pad the batch to this num of proofs with dummy proofs, to match a recursive vk for a bigger batch
+    #[clap(long = "pad-to")]
+    pad_to: Option<usize>,
+    /// Proof file to repeat when padding the batch [default: the last proof]
+    #[clap(long = "padding-proof")]
+    padding_proof: Option<String>,
+    /// Index of the vk of the padding proof
+    #[clap(long = "padding-vk-idx", default_value = "0")]
+    padding_vk_idx: usize,
+    /// Pad with valid proofs of the dummy circuit instead, whose vk (see `export-verification-key --dummy-inputs`) is at padding-vk-idx
+    #[clap(long = "dummy-proofs")]
+    dummy_proofs: bool,
+    /// Recursive verification key the proof is for, to check the parameters against its metadata
+    #[clap(short = "r", long = "recursive_vk")]
+    recursive_vk: Option<String>,
+    /// Skip checking the parameters against the metadata of the recursive vk, even when it has one
+    #[clap(long = "skip-vk-metadata")]
+    skip_vk_metadata: bool,
     /// Output file for aggregated proof BIN
     #[clap(short = "n", long = "new_proof", default_value = "recursive_proof.bin")]
     new_proof: String,
@@ -233,26 +362,223 @@ struct RecursiveProveOpts {
 /// A subcommand for verifying recursive proof
 #[derive(Clap)]
 struct RecursiveVerifyOpts {
-    /// Aggregated Proof BIN file
+    /// Aggregated Proof BIN or JSON file
     #[clap(short = "p", long = "proof", default_value = "recursive_proof.bin")]
     proof: String,
-    /// Aggregated verification key file
+    /// Aggregated proof format, bin or json
+    #[clap(short = "f", long = "format", default_value = "bin")]
+    format: String,
+    /// Aggregated verification key BIN or JSON file
     #[clap(short = "v", long = "verification_key", default_value = "recursive_vk.bin")]
     vk: String,
+    /// Skip checking the parameters against the metadata of the recursive vk, even when it has one
+    #[clap(long = "skip-vk-metadata")]
+    skip_vk_metadata: bool,
 }
 
 /// A subcommand for checking an aggregated proof is corresponding to the original proofs
 #[derive(Clap)]
 struct CheckAggregationOpts {
-    /// Old proof file list text file
+    /// Old proof file list text file, each line is a proof file optionally followed by the index of its vk
     #[clap(short = "o", long = "old_proof_list")]
     old_proof_list: String,
     /// Old vk
     #[clap(short = "v", long = "old_vk", default_value = "vk.bin")]
     old_vk: String,
-    /// Aggregated Proof BIN file
+    /// Old vk list text file, used instead of old_vk when aggregating proofs of different circuits
+    #[clap(short = "l", long = "old_vk_list")]
+    old_vk_list: Option<String>,
+    /// Depth of the vk tree, which holds up to 2^depth vks
+    #[clap(short = "d", long = "vk_tree_depth", default_value = "7")]
+    vk_tree_depth: usize,
+    /// Aggregated Proof BIN or JSON file
     #[clap(short = "n", long = "new_proof", default_value = "recursive_proof.bin")]
     new_proof: String,
+    /// Aggregated proof format, bin or json
+    #[clap(short = "f", long = "format", default_value = "bin")]
+    format: String,
+    /// Recursive verification key the proof is for, to check the vk tree depth against its metadata
+    #[clap(long = "recursive_vk", default_value = "recursive_vk.bin")]
+    recursive_vk: String,
+    /// Skip checking the parameters against the metadata of the recursive vk, even when it has one
+    #[clap(long = "skip-vk-metadata")]
+    skip_vk_metadata: bool,
+    /// Output file for the check report json
+    #[clap(short = "r", long = "report")]
+    report: Option<String>,
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+}
+
+/// A subcommand for generating calldata of a verifier smart contract
+#[derive(Clap)]
+struct CalldataOpts {
+    /// Proof BIN file
+    #[clap(short = "p", long = "proof", default_value = "proof.bin")]
+    proof: String,
+    /// Whether the proof is an aggregated proof for a recursive verifier
+    #[clap(short = "r", long = "recursive")]
+    recursive: bool,
+    /// Output file for hex encoded calldata
+    #[clap(short = "o", long = "output", default_value = "calldata.hex")]
+    output: String,
+    /// Output file for the json argument array
+    #[clap(short = "j", long = "argsjson", default_value = "calldata.json")]
+    argsjson: String,
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+}
+
+/// A subcommand for verifying a proof with a verifier smart contract in an embedded EVM
+#[cfg(feature = "evm")]
+#[derive(Clap)]
+struct VerifyEvmOpts {
+    /// Verifier contract, a solidity file (compiled with solc on PATH) or a file of hex encoded bytecode
+    #[clap(short = "c", long = "contract", default_value = "verifier.sol")]
+    contract: String,
+    /// Contract name [default: KeyedVerifier|KeysWithPlonkVerifier]
+    #[clap(short = "n", long = "name")]
+    name: Option<String>,
+    /// Whether the proof is an aggregated proof for a recursive verifier
+    #[clap(short = "r", long = "recursive")]
+    recursive: bool,
+    /// Proof json file
+    #[clap(short = "j", long = "proofjson", default_value = "proof.json")]
+    proofjson: String,
+    /// Public input json file, unused for recursive proofs
+    #[clap(short = "i", long = "publicjson", default_value = "public.json")]
+    publicjson: String,
+}
+
+/// A subcommand for benchmarking the proving phases of a circuit
+#[derive(Clap)]
+struct BenchOpts {
+    /// Source file for Plonk universal setup srs in monomial form
+    #[clap(short = "m", long = "srs_monomial_form")]
+    srs_monomial_form: String,
+    /// Source file for Plonk universal setup srs in lagrange form [default: computed from the monomial form]
+    #[clap(short = "l", long = "srs_lagrange_form")]
+    srs_lagrange_form: Option<String>,
+    /// Circuit R1CS or JSON file [default: circuit.r1cs|circuit.json]
+    #[clap(short = "c", long = "circuit")]
+    circuit: Option<String>,
+    /// Witness BIN or JSON file
+    #[clap(short = "w", long = "witness", default_value = "witness.wtns")]
+    witness: String,
+    /// Num of repetitions
+    #[clap(short = "n", long = "repetitions", default_value = "3")]
+    repetitions: usize,
+    /// SRS forms to prove with, separated by commas
+    #[clap(short = "s", long = "srs_forms", default_value = "monomial,lagrange")]
+    srs_forms: String,
+    /// Transcripts to prove with, separated by commas
+    #[clap(short = "t", long = "transcripts", default_value = "keccak,rescue")]
+    transcripts: String,
+    /// Output file
+    #[clap(short = "o", long = "output", default_value = "bench.json")]
+    output: String,
+}
+
+/// A subcommand for optimising the R1CS of a circuit
+#[derive(Clap)]
+struct OptimiseR1csOpts {
+    /// Circuit R1CS or JSON file [default: circuit.r1cs|circuit.json]
+    #[clap(short = "c", long = "circuit")]
+    circuit: Option<String>,
+    /// Output file for the optimised circuit JSON
+    #[clap(short = "o", long = "output", default_value = "circuit_optimised.r1cs.json")]
+    output: String,
+    /// Output file for the wire mapping, the witness wire of each wire of the optimised circuit
+    #[clap(short = "m", long = "wire_mapping", default_value = "wire_mapping.json")]
+    wire_mapping: String,
+    /// Transpile both circuits to report their gate counts, which takes as long as a setup
+    #[clap(long = "gates")]
+    gates: bool,
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+}
+
+/// A subcommand for running the whole proving pipeline of a manifest
+#[derive(Clap)]
+struct PipelineOpts {
+    #[clap(long = "overwrite")]
+    overwrite: bool,
+}
+
+/// A subcommand for the proving job queue
+#[derive(Clap)]
+struct QueueOpts {
+    /// Queue directory, holding a json file for each job
+    #[clap(short = "q", long = "queue", default_value = "plonkit-queue")]
+    queue: String,
+    #[clap(subcommand)]
+    command: QueueCommand,
+}
+
+#[derive(Clap)]
+enum QueueCommand {
+    /// Queue a prove or recursive-prove job, e.g. `plonkit queue submit -- prove -m setup_2^20.key`
+    Submit(QueueSubmitOpts),
+    /// Run the queued jobs, retrying interrupted and failed ones
+    Run(QueueRunOpts),
+    /// Print the status of the jobs
+    Status(QueueStatusOpts),
+}
+
+#[derive(Clap)]
+struct QueueSubmitOpts {
+    /// The subcommand to queue and its arguments
+    #[clap(last = true)]
+    args: Vec<String>,
+}
+
+#[derive(Clap)]
+struct QueueRunOpts {
+    /// Max attempts of a job, including the interrupted ones
+    #[clap(long = "max-attempts", default_value = "3")]
+    max_attempts: u32,
+}
+
+#[derive(Clap)]
+struct QueueStatusOpts {
+    /// Job id [default: all the jobs]
+    #[clap(short = "i", long = "id")]
+    id: Option<u64>,
+}
+
+/// A subcommand for serving proving requests over HTTP
+#[cfg(feature = "serve")]
+#[derive(Clap)]
+struct ServeOpts {
+    /// Address to listen on
+    #[clap(short = "a", long = "addr", default_value = "127.0.0.1:8080")]
+    addr: String,
+    /// Circuit R1CS or JSON files to serve, separated by commas [default: circuit.r1cs|circuit.json]
+    #[clap(short = "c", long = "circuit")]
+    circuit: Option<String>,
+    /// Source file for Plonk universal setup srs in monomial form
+    #[clap(short = "m", long = "srs_monomial_form")]
+    srs_monomial_form: String,
+    /// Source file for Plonk universal setup srs in lagrange form
+    #[clap(short = "l", long = "srs_lagrange_form")]
+    srs_lagrange_form: Option<String>,
+    /// Transcript can be keccak or rescue, keccak default
+    #[clap(short = "t", long = "transcript", default_value = "keccak")]
+    transcript: String,
+}
+
+/// A subcommand for estimating the on-chain verification gas of a verifier smart contract
+#[derive(Clap)]
+struct EstimateGasOpts {
+    /// Verification key file, or the recursive vk file for the recursive verifier
+    #[clap(short = "v", long = "verification_key", default_value = "vk.bin")]
+    vk: String,
+    /// Estimate for the recursive verifier instead, with the number of proofs and inputs from the recursive vk metadata
+    #[clap(short = "r", long = "recursive")]
+    recursive: bool,
+    /// Output file
+    #[clap(short = "o", long = "output", default_value = "gas.json")]
+    output: String,
 }
 
 fn main() {
@@ -268,8 +594,45 @@ fn main() {
     }
     env_logger::init();
 
-    let opts: Opts = Opts::parse();
-    match opts.command {
+    let args: Vec<String> = std::env::args().collect();
+    let command = args.iter().skip(1).find(|a| !a.starts_with('-')).cloned().unwrap_or_default();
+    // whether to print json is known from the parsed arguments, an invalid command line is reported as text
+    output::init(&command, false);
+    // a panic is an unexpected error, unless it happens in a worker thread which recovers from it
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        if std::thread::current().name() == Some("main") {
+            let error = info
+                .payload()
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| info.payload().downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+            output::fail(output::EXIT_ERROR, &error);
+        }
+    }));
+
+    let manifest = Manifest::maybe_load().expect("load manifest err");
+    let args = match &manifest {
+        Some(manifest) => manifest.apply(args),
+        None => args,
+    };
+    let opts: Opts = Opts::try_parse_from(args).unwrap_or_else(|e| {
+        if !e.use_stderr() {
+            // help and version
+            e.exit();
+        }
+        eprintln!("{}", e);
+        output::fail(output::EXIT_USAGE, "invalid arguments");
+    });
+    output::set_json(opts.json);
+    run(opts.command, manifest.as_ref());
+    output::finish();
+}
+
+fn run(command: SubCommand, manifest: Option<&Manifest>) {
+    match command {
         SubCommand::Analyse(o) => {
             analyse(o);
         }
@@ -288,12 +651,18 @@ fn main() {
         SubCommand::GenerateVerifier(o) => {
             generate_verifier(o);
         }
+        SubCommand::GenerateRustVerifier(o) => {
+            generate_rust_verifier(o);
+        }
         SubCommand::GenerateRecursiveVerifier(o) => {
             generate_recursive_verifier(o);
         }
         SubCommand::ExportVerificationKey(o) => {
             export_vk(o);
         }
+        SubCommand::ExportRecursiveProvingKey(o) => {
+            export_recursive_pk(o);
+        }
         SubCommand::ExportRecursiveVerificationKey(o) => {
             export_recursive_vk(o);
         }
@@ -306,6 +675,32 @@ fn main() {
         SubCommand::CheckAggregation(o) => {
             check_aggregation(o);
         }
+        SubCommand::Calldata(o) => {
+            calldata(o);
+        }
+        #[cfg(feature = "evm")]
+        SubCommand::VerifyEvm(o) => {
+            verify_evm(o);
+        }
+        SubCommand::EstimateGas(o) => {
+            estimate_gas(o);
+        }
+        #[cfg(feature = "serve")]
+        SubCommand::Serve(o) => {
+            serve(o);
+        }
+        SubCommand::Queue(o) => {
+            queue(o, manifest);
+        }
+        SubCommand::Bench(o) => {
+            bench(o);
+        }
+        SubCommand::OptimiseR1cs(o) => {
+            optimise_r1cs(o);
+        }
+        SubCommand::Pipeline(o) => {
+            pipeline(o, manifest.expect("pipeline needs a plonkit.toml manifest"));
+        }
     }
 }
 
@@ -313,21 +708,57 @@ fn main() {
 fn analyse(opts: AnalyseOpts) {
     let circuit_file = resolve_circuit_file(opts.circuit);
     log::info!("Loading circuit from {}...", circuit_file);
+    let r1cs = reader::load_r1cs(&circuit_file);
+    let constraint_components = opts.sym.as_ref().map(|sym| {
+        log::info!("Loading symbols from {}...", sym);
+        let wire_names = reader::load_symbols(sym).expect("load symbols failed");
+        plonkit::circom_circuit::constraint_components(&r1cs, &wire_names)
+    });
     let circuit = CircomCircuit {
-        r1cs: reader::load_r1cs(&circuit_file),
+        r1cs,
         witness: None,
         wire_mapping: None,
         aux_offset: plonk::AUX_OFFSET,
+        constraint_components,
     };
-    let mut stats = plonk::analyse(circuit).expect("analyse failed");
+    let calibration = opts.estimate_time.then(|| {
+        log::info!("Timing a multiexp and an FFT...");
+        plonk::Calibration::measure().expect("calibration failed")
+    });
+    let mut stats = plonk::analyse(circuit, calibration.as_ref()).expect("analyse failed");
     let writer = File::create(&opts.output).unwrap();
     serde_json::to_writer_pretty(writer, &stats).expect("write failed");
+    if let Some(folded) = &opts.folded {
+        assert!(
+            !stats.components.is_empty(),
+            "folded stacks need the symbols of the circuit, see --sym"
+        );
+        std::fs::write(folded, plonkit::transpile::folded_stacks(&stats.components)).expect("write failed");
+        log::info!("folded stacks saved to {}", folded);
+        output::written(folded);
+    }
     stats.constraint_stats.clear();
+    stats.components.clear();
     log::info!(
         "analyse result: {}",
         serde_json::to_string_pretty(&stats).unwrap_or_else(|_| "<failed>".to_owned())
     );
     log::info!("output to {}", opts.output);
+    output::written(&opts.output);
+    let resources = &stats.resources;
+    let secs = |secs: Option<f64>| secs.map_or_else(String::new, |secs| format!(" {:.1} secs", secs));
+    log::info!(
+        "needs a 2^{} domain and at least {}, estimated proving: {} MiB{}, dump-lagrange: {} MiB{}",
+        resources.domain_power,
+        resources.srs_monomial_form,
+        resources.prove_memory_bytes >> 20,
+        secs(resources.prove_secs),
+        resources.dump_lagrange_memory_bytes >> 20,
+        secs(resources.dump_lagrange_secs)
+    );
+    if let Err(e) = stats.check_setup_range() {
+        output::fail(output::EXIT_SETUP_TOO_LARGE, &e.to_string());
+    }
 }
 
 // generate a monomial_form SRS, and save it to a file
@@ -340,6 +771,7 @@ fn setup(opts: SetupOpts) {
     let writer = File::create(&opts.srs_monomial_form).unwrap();
     srs.write(writer).unwrap();
     log::info!("srs_monomial_form saved to {}", opts.srs_monomial_form);
+    output::written(&opts.srs_monomial_form);
 }
 
 // circuit filename default resolver
@@ -356,6 +788,52 @@ fn resolve_circuit_file(filename: Option<String>) -> String {
     }
 }
 
+// the metadata of a recursive vk to check against, unless its checks are skipped. A vk exported before the metadata
+// has none, and is used without the checks
+fn load_recursive_vk_metadata(vk_file: &str, skip: bool) -> Option<recursive::RecursiveVkMetadata> {
+    if skip {
+        log::warn!("not checking the metadata of recursive vk {}", vk_file);
+        return None;
+    }
+    match reader::maybe_load_recursive_vk_metadata(vk_file) {
+        Ok(Some(metadata)) => Some(metadata),
+        Ok(None) => {
+            log::warn!(
+                "recursive vk {} has no metadata ({}), not checking the proofs and the vk tree depth against it",
+                vk_file,
+                recursive::RecursiveVkMetadata::path_for(vk_file).display()
+            );
+            None
+        }
+        Err(e) => output::fail(output::EXIT_ERROR, &e.to_string()),
+    }
+}
+
+// load an aggregated proof in the given format
+fn load_aggregated_proof(filename: &str, format: &str, n: usize) -> recursive::PaddedAggregatedProof {
+    match format {
+        "bin" => reader::load_aggregated_proof(filename),
+        "json" => reader::load_aggregated_proof_from_json(filename, n),
+        _ => panic!("unsupported aggregated proof format: {}", format),
+    }
+}
+
+// the designated padding proof of a batch, if any
+fn load_padding_proof(
+    padding_proof: &Option<String>,
+    padding_vk_idx: usize,
+) -> Option<(Proof<Bn256, PlonkCsWidth4WithNextStepParams>, usize)> {
+    padding_proof.as_ref().map(|f| (reader::load_proof::<Bn256>(f), padding_vk_idx))
+}
+
+// old vks of aggregated proofs: either the vk list, or the single vk
+fn load_old_vks(old_vk: &str, old_vk_list: Option<String>) -> Vec<VerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>> {
+    match old_vk_list {
+        Some(list) => reader::load_verification_keys_from_list::<Bn256>(&list),
+        None => vec![reader::load_verification_key::<Bn256>(old_vk)],
+    }
+}
+
 // generate a lagrange_form SRS from a monomial_form SRS, and save it to a file
 fn dump_lagrange(opts: DumpLagrangeOpts) {
     let circuit_file = resolve_circuit_file(opts.circuit);
@@ -365,6 +843,7 @@ fn dump_lagrange(opts: DumpLagrangeOpts) {
         witness: None,
         wire_mapping: None,
         aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
     };
 
     let setup = plonk::SetupForProver::prepare_setup_for_prover(circuit, reader::load_key_monomial_form(&opts.srs_monomial_form), None)
@@ -378,28 +857,45 @@ fn dump_lagrange(opts: DumpLagrangeOpts) {
     let writer = File::create(&opts.srs_lagrange_form).unwrap();
     key_lagrange_form.write(writer).unwrap();
     log::info!("srs_lagrange_form saved to {}", opts.srs_lagrange_form);
+    output::written(&opts.srs_lagrange_form);
 }
 
 // generate a plonk proof for a circuit, with witness loaded, and save the proof to a file
 fn prove(opts: ProveOpts) {
     let circuit_file = resolve_circuit_file(opts.circuit);
     log::info!("Loading circuit from {}...", circuit_file);
-    let circuit = CircomCircuit {
-        r1cs: reader::load_r1cs(&circuit_file),
-        witness: Some(reader::load_witness_from_file::<Bn256>(&opts.witness)),
-        wire_mapping: None,
-        aux_offset: plonk::AUX_OFFSET,
-    };
+    let proof = if is_gate_circuit_file(&circuit_file) {
+        assert!(
+            opts.srs_lagrange_form.is_none(),
+            "native circuits are proved with the monomial_form SRS only"
+        );
+        let mut circuit = reader::load_gate_circuit(&circuit_file);
+        circuit.witness = Some(reader::load_witness_from_file::<Bn256>(&opts.witness));
+        let setup =
+            plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, reader::load_key_monomial_form(&opts.srs_monomial_form))
+                .expect("prepare err");
 
-    let setup = plonk::SetupForProver::prepare_setup_for_prover(
-        circuit.clone(),
-        reader::load_key_monomial_form(&opts.srs_monomial_form),
-        reader::maybe_load_key_lagrange_form(opts.srs_lagrange_form),
-    )
-    .expect("prepare err");
+        log::info!("Proving...");
+        setup.prove_native(&circuit, &opts.transcript).unwrap()
+    } else {
+        let circuit = CircomCircuit {
+            r1cs: reader::load_r1cs(&circuit_file),
+            witness: Some(reader::load_witness_from_file::<Bn256>(&opts.witness)),
+            wire_mapping: opts.wire_mapping.as_deref().map(reader::load_wire_mapping),
+            aux_offset: plonk::AUX_OFFSET,
+            constraint_components: None,
+        };
 
-    log::info!("Proving...");
-    let proof = setup.prove(circuit, &opts.transcript).unwrap();
+        let setup = plonk::SetupForProver::prepare_setup_for_prover(
+            circuit.clone(),
+            reader::load_key_monomial_form(&opts.srs_monomial_form),
+            reader::maybe_load_key_lagrange_form(opts.srs_lagrange_form),
+        )
+        .expect("prepare err");
+
+        log::info!("Proving...");
+        setup.prove(circuit, &opts.transcript).unwrap()
+    };
     if !opts.overwrite {
         let path = Path::new(&opts.proof);
         assert!(!path.exists(), "duplicate proof file: {}", path.display());
@@ -407,6 +903,7 @@ fn prove(opts: ProveOpts) {
     let writer = File::create(&opts.proof).unwrap();
     proof.write(writer).unwrap();
     log::info!("Proof saved to {}", opts.proof);
+    output::written(&opts.proof);
 
     let (inputs, serialized_proof) = bellman_vk_codegen::serialize_proof(&proof);
     let ser_proof_str = serde_json::to_string_pretty(&serialized_proof).unwrap();
@@ -419,8 +916,10 @@ fn prove(opts: ProveOpts) {
     }
     std::fs::write(&opts.proofjson, ser_proof_str.as_bytes()).expect("save proofjson err");
     log::info!("Proof json saved to {}", opts.proofjson);
+    output::written(&opts.proofjson);
     std::fs::write(&opts.publicjson, ser_inputs_str.as_bytes()).expect("save publicjson err");
     log::info!("Public input json saved to {}", opts.publicjson);
+    output::written(&opts.publicjson);
 }
 
 // verify a plonk proof by using a verification key
@@ -429,11 +928,11 @@ fn verify(opts: VerifyOpts) {
 
     let proof = reader::load_proof::<Bn256>(&opts.proof);
     let correct = plonk::verify(&vk, &proof, &opts.transcript).expect("fail to verify proof");
+    output::set("valid", &correct);
     if correct {
         log::info!("Proof is valid.");
     } else {
-        log::info!("Proof is invalid!");
-        std::process::exit(400);
+        output::fail(output::EXIT_INVALID_PROOF, "Proof is invalid!");
     }
 }
 
@@ -453,14 +952,34 @@ fn generate_verifier(opts: GenerateVerifierOpts) {
         }
     }
     log::info!("Contract saved to {}", opts.sol);
+    output::written(&opts.sol);
+}
+
+// generate a rust verifier crate by feeding a verification key, and save it to a directory
+fn generate_rust_verifier(opts: GenerateRustVerifierOpts) {
+    let vk = reader::load_verification_key::<Bn256>(&opts.vk);
+    if !opts.overwrite {
+        let path = Path::new(&opts.output);
+        assert!(!path.exists(), "duplicate output directory: {}", path.display());
+    }
+    rust_verifier::render_verifier_crate(&vk, &opts.name, &opts.output).expect("generate rust verifier err");
+    log::info!("Rust verifier crate saved to {}", opts.output);
+    output::written(&opts.output);
 }
 
 // generate a solidity plonk verifier for proof recursion
 fn generate_recursive_verifier(opts: GenerateRecursiveVerifierOpts) {
-    let old_vk = reader::load_verification_key::<Bn256>(&opts.old_vk);
+    let old_vks = load_old_vks(&opts.old_vk, opts.old_vk_list);
+    if let Some(metadata) = load_recursive_vk_metadata(&opts.new_vk, opts.skip_vk_metadata) {
+        assert_eq!(
+            metadata.vk_tree_depth, opts.vk_tree_depth,
+            "vk tree depth mismatch with the recursive vk"
+        );
+        assert_eq!(metadata.num_inputs, opts.num_inputs, "num_inputs mismatch with the recursive vk");
+    }
     let recursive_vk = reader::load_recursive_verification_key(&opts.new_vk);
     let config = recurisive_vk_codegen::Config {
-        vk_tree_root: recursive::get_vk_tree_root_hash(old_vk).unwrap(),
+        vk_tree_root: recursive::get_vk_tree_root_hash(&old_vks, opts.vk_tree_depth).unwrap(),
         //vk_max_index: 0, //because we has aggregated only 1 vk
         individual_input_num: opts.num_inputs,
         recursive_vk,
@@ -478,21 +997,36 @@ fn generate_recursive_verifier(opts: GenerateRecursiveVerifierOpts) {
         }
     }
     log::info!("Contract saved to {}", opts.sol);
+    output::written(&opts.sol);
 }
 
 // export a verification key for a circuit, and save it to a file
 fn export_vk(opts: ExportVerificationKeyOpts) {
-    let circuit_file = resolve_circuit_file(opts.circuit);
-    log::info!("Loading circuit from {}...", circuit_file);
-    let circuit = CircomCircuit {
-        r1cs: reader::load_r1cs(&circuit_file),
-        witness: None,
-        wire_mapping: None,
-        aux_offset: plonk::AUX_OFFSET,
+    let key_monomial_form = reader::load_key_monomial_form(&opts.srs_monomial_form);
+    let setup = match opts.dummy_inputs {
+        Some(num_inputs) => {
+            log::info!("Exporting the vk of the dummy circuit with {} inputs...", num_inputs);
+            let circuit = GateCircuit::<Bn256>::dummy(num_inputs);
+            plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, key_monomial_form).expect("prepare err")
+        }
+        None => {
+            let circuit_file = resolve_circuit_file(opts.circuit);
+            log::info!("Loading circuit from {}...", circuit_file);
+            if is_gate_circuit_file(&circuit_file) {
+                let circuit = reader::load_gate_circuit(&circuit_file);
+                plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, key_monomial_form).expect("prepare err")
+            } else {
+                let circuit = CircomCircuit {
+                    r1cs: reader::load_r1cs(&circuit_file),
+                    witness: None,
+                    wire_mapping: None,
+                    aux_offset: plonk::AUX_OFFSET,
+                    constraint_components: None,
+                };
+                plonk::SetupForProver::prepare_setup_for_prover(circuit, key_monomial_form, None).expect("prepare err")
+            }
+        }
     };
-
-    let setup = plonk::SetupForProver::prepare_setup_for_prover(circuit, reader::load_key_monomial_form(&opts.srs_monomial_form), None)
-        .expect("prepare err");
     let vk = setup.make_verification_key().unwrap();
     if !opts.overwrite {
         let path = Path::new(&opts.vk);
@@ -501,69 +1035,519 @@ fn export_vk(opts: ExportVerificationKeyOpts) {
     let writer = File::create(&opts.vk).unwrap();
     vk.write(writer).unwrap();
     log::info!("Verification key saved to {}", opts.vk);
+    output::written(&opts.vk);
 }
 
-// export a verification key for a recursion circuit, and save it to a file
+// export the setup of a recursion circuit, and save it to a file
+fn export_recursive_pk(opts: ExportRecursiveProvingKeyOpts) {
+    let pk =
+        recursive::export_pk(opts.num_proofs_to_check, opts.num_inputs, opts.vk_tree_depth).expect("must create recursive circuit setup");
+    if !opts.overwrite {
+        let path = Path::new(&opts.pk);
+        assert!(!path.exists(), "duplicate pk file: {}", path.display());
+    }
+    let writer = BufWriter::new(File::create(&opts.pk).unwrap());
+    pk.write(writer).unwrap();
+    log::info!("Recursive proving key saved to {}", opts.pk);
+    output::written(&opts.pk);
+}
+
+// export a verification key for a recursion circuit (from its saved setup if given), and save it to a file
 fn export_recursive_vk(opts: ExportRecursiveVerificationKeyOpts) {
     let big_crs = reader::load_key_monomial_form(&opts.srs_monomial_form);
-    let vk =
-        recursive::export_vk(opts.num_proofs_to_check, opts.num_inputs, &big_crs).expect("must create recursive circuit verification key");
+    let vk = match &opts.pk {
+        Some(pk_file) => {
+            let pk = reader::load_recursive_proving_key(pk_file);
+            assert_eq!(
+                pk.num_proofs_to_check, opts.num_proofs_to_check,
+                "num of proofs mismatch with the proving key"
+            );
+            assert_eq!(pk.num_inputs, opts.num_inputs, "num_inputs mismatch with the proving key");
+            assert_eq!(pk.vk_tree_depth, opts.vk_tree_depth, "vk tree depth mismatch with the proving key");
+            recursive::export_vk_from_pk(&pk, &big_crs)
+        }
+        None => recursive::export_vk(opts.num_proofs_to_check, opts.num_inputs, opts.vk_tree_depth, &big_crs),
+    }
+    .expect("must create recursive circuit verification key");
+    let metadata_path = recursive::RecursiveVkMetadata::path_for(&opts.vk);
     if !opts.overwrite {
         let path = Path::new(&opts.vk);
         assert!(!path.exists(), "duplicate vk file: {}", path.display());
+        assert!(!metadata_path.exists(), "duplicate vk metadata file: {}", metadata_path.display());
     }
     let writer = File::create(&opts.vk).unwrap();
     vk.write(writer).unwrap();
     log::info!("Recursive verification key saved to {}", opts.vk);
+    output::written(&opts.vk);
+    if let Some(vkjson) = &opts.vkjson {
+        if !opts.overwrite {
+            let path = Path::new(vkjson);
+            assert!(!path.exists(), "duplicate vk json file: {}", path.display());
+        }
+        let writer = File::create(vkjson).unwrap();
+        serde_json::to_writer_pretty(writer, &vk).expect("save vk json err");
+        log::info!("Recursive verification key json saved to {}", vkjson);
+        output::written(vkjson);
+    }
+
+    let metadata = recursive::RecursiveVkMetadata {
+        num_proofs_to_check: opts.num_proofs_to_check,
+        num_inputs: opts.num_inputs,
+        vk_tree_depth: opts.vk_tree_depth,
+    };
+    let writer = File::create(&metadata_path).unwrap();
+    serde_json::to_writer_pretty(writer, &metadata).expect("save vk metadata err");
+    log::info!("Recursive verification key metadata saved to {}", metadata_path.display());
+    output::written(&metadata_path);
 }
 
 // recursively prove multiple proofs, and aggregate them into one, and save the proof to a file
 fn recursive_prove(opts: RecursiveProveOpts) {
-    let big_crs = reader::load_key_monomial_form(&opts.srs_monomial_form);
-    let old_proofs = reader::load_proofs_from_list::<Bn256>(&opts.old_proof_list);
-    let old_vk = reader::load_verification_key::<Bn256>(&opts.old_vk);
-    let proof = recursive::prove(big_crs, old_proofs, old_vk).unwrap();
+    let mut big_crs = reader::load_key_monomial_form(&opts.srs_monomial_form);
+    let (mut old_proofs, mut vk_idxs) = reader::load_proofs_and_vk_idxs_from_list::<Bn256>(&opts.old_proof_list);
+    let old_vks = load_old_vks(&opts.old_vk, opts.old_vk_list);
+    let padding = match opts.pad_to {
+        Some(pad_to) => {
+            let padding_proof = if opts.dummy_proofs {
+                assert!(opts.padding_proof.is_none(), "padding with dummy proofs, not a padding proof file");
+                let num_inputs = match old_proofs.first() {
+                    Some(proof) => proof.num_inputs,
+                    None => output::fail(output::EXIT_ERROR, "no proof to pad with dummy proofs"),
+                };
+                let dummy_vk = old_vks.get(opts.padding_vk_idx).expect("padding vk index out of the vk list");
+                let (dummy_proof, crs) = recursive::prove_dummy(big_crs, num_inputs, dummy_vk).expect("fail to prove the dummy circuit");
+                big_crs = crs;
+                Some((dummy_proof, opts.padding_vk_idx))
+            } else {
+                load_padding_proof(&opts.padding_proof, opts.padding_vk_idx)
+            };
+            Some(recursive::pad_proofs(&mut old_proofs, &mut vk_idxs, pad_to, padding_proof).expect("fail to pad proofs"))
+        }
+        None => None,
+    };
+    if let Some(metadata) = opts
+        .recursive_vk
+        .as_deref()
+        .and_then(|vk| load_recursive_vk_metadata(vk, opts.skip_vk_metadata))
+    {
+        assert_eq!(
+            metadata.vk_tree_depth, opts.vk_tree_depth,
+            "vk tree depth mismatch with the recursive vk"
+        );
+        assert_eq!(
+            metadata.num_proofs_to_check,
+            old_proofs.len(),
+            "num of proofs mismatch with the recursive vk"
+        );
+        assert_eq!(
+            metadata.num_inputs, old_proofs[0].num_inputs,
+            "num_inputs mismatch with the recursive vk"
+        );
+    }
+    let pk = opts.pk.as_deref().map(reader::load_recursive_proving_key);
+    let proof = match recursive::prove(big_crs, old_proofs, old_vks, vk_idxs, opts.vk_tree_depth, pk) {
+        Ok(proof) => proof,
+        Err(e) => output::fail(output::EXIT_AGGREGATION_REJECTED, &format!("fail to aggregate proofs: {}", e)),
+    };
     if !opts.overwrite {
         let path = Path::new(&opts.new_proof);
         assert!(!path.exists(), "duplicate proof file: {}", path.display());
         let path = Path::new(&opts.proofjson);
         assert!(!path.exists(), "duplicate proof json file: {}", path.display());
     }
+    if let Some(padding) = &padding {
+        log::info!("{} of {} aggregated proofs are dummies", padding.num_padding, padding.num_proofs);
+    }
+    let proof = recursive::PaddedAggregatedProof::new(proof, padding).expect("padding mismatch with the aggregated proof");
     let writer = File::create(&opts.new_proof).unwrap();
     proof.write(writer).unwrap();
     log::info!("Proof saved to {}", opts.new_proof);
+    output::written(&opts.new_proof);
 
-    let ser_proof_str = serde_json::to_string_pretty(&proof).unwrap();
+    let ser_proof_str = serde_json::to_string_pretty(&proof.to_json().expect("serialize proof json err")).unwrap();
     std::fs::write(&opts.proofjson, ser_proof_str.as_bytes()).expect("save proofjson err");
     log::info!("Proof json saved to {}", opts.proofjson);
+    output::written(&opts.proofjson);
 }
 
 // verify a recursive proof by using a corresponding verification key
 fn recursive_verify(opts: RecursiveVerifyOpts) {
     let vk = reader::load_recursive_verification_key(&opts.vk);
-    let proof = reader::load_aggregated_proof(&opts.proof);
-    let correct = recursive::verify(vk, proof).expect("fail to verify recursive proof");
+    let proof = load_aggregated_proof(&opts.proof, &opts.format, vk.n);
+    if let Some(metadata) = load_recursive_vk_metadata(&opts.vk, opts.skip_vk_metadata) {
+        if let Err(e) = metadata.check_proof(&proof.proof) {
+            output::set("valid", &false);
+            output::fail(output::EXIT_INVALID_PROOF, &format!("Proof doesn't match the recursive vk: {}", e));
+        }
+    }
+    if let Some(padding) = &proof.padding {
+        log::info!("{} of {} aggregated proofs are real", padding.num_real_proofs(), padding.num_proofs);
+        output::set("num_real_proofs", &padding.num_real_proofs());
+    }
+    let correct = recursive::verify(vk, proof.proof).expect("fail to verify recursive proof");
+    output::set("valid", &correct);
     if correct {
         log::info!("Proof is valid.");
     } else {
-        log::info!("Proof is invalid!");
-        std::process::exit(400);
+        output::fail(output::EXIT_INVALID_PROOF, "Proof is invalid!");
     }
 }
 
 // check an aggregated proof is corresponding to the original proofs
 fn check_aggregation(opts: CheckAggregationOpts) {
-    let old_proofs = reader::load_proofs_from_list::<Bn256>(&opts.old_proof_list);
-    let old_vk = reader::load_verification_key::<Bn256>(&opts.old_vk);
-    let new_proof = reader::load_aggregated_proof(&opts.new_proof);
+    let (old_proofs, vk_idxs) = reader::load_proofs_and_vk_idxs_from_list::<Bn256>(&opts.old_proof_list);
+    let old_vks = load_old_vks(&opts.old_vk, opts.old_vk_list);
+    // the domain size of the aggregated proof doesn't matter here
+    let new_proof = load_aggregated_proof(&opts.new_proof, &opts.format, 0);
+    if let Some(metadata) = load_recursive_vk_metadata(&opts.recursive_vk, opts.skip_vk_metadata) {
+        if metadata.vk_tree_depth != opts.vk_tree_depth {
+            output::fail(
+                output::EXIT_CHECK_FAILED,
+                &format!(
+                    "vk tree depth {} mismatch with the recursive vk {}",
+                    opts.vk_tree_depth, metadata.vk_tree_depth
+                ),
+            );
+        }
+        if let Err(e) = metadata.check_proof(&new_proof.proof) {
+            output::fail(
+                output::EXIT_CHECK_FAILED,
+                &format!("aggregated proof doesn't match the recursive vk: {}", e),
+            );
+        }
+    }
 
-    let expected = recursive::get_aggregated_input(old_proofs, old_vk).expect("fail to get aggregated input");
-    log::info!("hash to input: {:?}", expected);
-    log::info!("new_proof's input: {:?}", new_proof.proof.inputs[0]);
+    // the proof list only has the real proofs, the dummy ones are marked in the aggregated proof by `recursive-prove`
+    if let Some(padding) = &new_proof.padding {
+        log::info!("excluding {} dummy proofs", padding.num_padding);
+    }
 
-    if expected == new_proof.proof.inputs[0] {
+    let report = match recursive::check_aggregation(old_proofs, old_vks, vk_idxs, opts.vk_tree_depth, &new_proof) {
+        Ok(report) => report,
+        Err(e) => output::fail(output::EXIT_CHECK_FAILED, &format!("fail to check aggregation: {}", e)),
+    };
+    if !report.num_proofs_match {
+        log::error!(
+            "the proof list doesn't have the {} real proofs of the aggregated proof",
+            report.num_real_proofs
+        );
+    }
+    log::info!("hash to input: {}", report.expected_input);
+    log::info!("new_proof's input: {}", report.actual_input);
+    for p in report.proofs.iter().filter(|p| !p.is_valid()) {
+        log::error!(
+            "proof {} mismatch: inputs match {}, vk index {:?} (expected {:?})",
+            p.index,
+            p.inputs_match,
+            p.actual_vk_idx,
+            p.expected_vk_idx
+        );
+    }
+    if let Some(report_file) = &opts.report {
+        if !opts.overwrite {
+            let path = Path::new(report_file);
+            assert!(!path.exists(), "duplicate report file: {}", path.display());
+        }
+        let writer = File::create(report_file).unwrap();
+        serde_json::to_writer_pretty(writer, &report).expect("save report err");
+        log::info!("Report saved to {}", report_file);
+        output::written(report_file);
+    }
+
+    if report.input_match {
         log::info!("Aggregation hash input match");
     } else {
         log::error!("Aggregation hash input mismatch");
     }
+    output::set("report", &report);
+    if !report.is_valid() {
+        output::fail(output::EXIT_CHECK_FAILED, "aggregation check failed");
+    }
+}
+
+// generate ABI-encoded calldata of a proof for the generated verifier contract, and save it to files
+fn calldata(opts: CalldataOpts) {
+    let calldata = if opts.recursive {
+        let proof = reader::load_aggregated_proof(&opts.proof);
+        plonkit::calldata::recursive_verifier_calldata(&proof.proof)
+    } else {
+        let proof = reader::load_proof::<Bn256>(&opts.proof);
+        plonkit::calldata::single_verifier_calldata(&proof)
+    }
+    .expect("fail to encode calldata");
+    if !opts.overwrite {
+        let path = Path::new(&opts.output);
+        assert!(!path.exists(), "duplicate calldata file: {}", path.display());
+        let path = Path::new(&opts.argsjson);
+        assert!(!path.exists(), "duplicate calldata json file: {}", path.display());
+    }
+    std::fs::write(&opts.output, calldata.to_hex().as_bytes()).expect("save calldata err");
+    log::info!("Calldata saved to {}", opts.output);
+    output::written(&opts.output);
+    let args_str = serde_json::to_string_pretty(&calldata.args).unwrap();
+    std::fs::write(&opts.argsjson, args_str.as_bytes()).expect("save calldata json err");
+    log::info!("Calldata json saved to {}", opts.argsjson);
+    output::written(&opts.argsjson);
+}
+
+#[cfg(feature = "evm")]
+fn verify_evm(opts: VerifyEvmOpts) {
+    let read_json = |path: &str| -> serde_json::Value {
+        let content = std::fs::read_to_string(path).expect("read json err");
+        serde_json::from_str(&content).expect("parse json err")
+    };
+    let (name, calldata) = if opts.recursive {
+        let proof = read_json(&opts.proofjson);
+        (
+            "KeysWithPlonkVerifier",
+            plonkit::calldata::recursive_verifier_calldata_from_json(&proof),
+        )
+    } else {
+        let inputs = read_json(&opts.publicjson);
+        let proof = read_json(&opts.proofjson);
+        (
+            "KeyedVerifier",
+            plonkit::calldata::single_verifier_calldata_from_json(&inputs, &proof),
+        )
+    };
+    let calldata = calldata.expect("fail to encode calldata");
+    let name = opts.name.as_deref().unwrap_or(name);
+
+    log::info!("Loading contract {} from {}...", name, opts.contract);
+    let bytecode = plonkit::evm::load_bytecode(&opts.contract, name).expect("load contract err");
+    let result = plonkit::evm::deploy_and_call(&bytecode, &calldata.data).expect("fail to run contract");
+    log::info!("Gas used: {}", result.gas_used);
+    output::set("valid", &result.valid);
+    output::set("gas_used", &result.gas_used);
+    if result.valid {
+        log::info!("Proof is valid.");
+    } else {
+        output::fail(output::EXIT_INVALID_PROOF, "Proof is invalid!");
+    }
+}
+
+// optimise the R1CS of a circuit, and save it with its wire mapping
+fn optimise_r1cs(opts: OptimiseR1csOpts) {
+    let circuit_file = resolve_circuit_file(opts.circuit);
+    log::info!("Loading circuit from {}...", circuit_file);
+    let r1cs = reader::load_r1cs(&circuit_file);
+    let optimised = plonkit::optimise::optimise(&r1cs);
+    let stats = &optimised.stats;
+    log::info!(
+        "constraints: {} -> {}, variables: {} -> {}",
+        stats.num_constraints_before,
+        stats.num_constraints_after,
+        stats.num_variables_before,
+        stats.num_variables_after
+    );
+    if opts.gates {
+        let num_gates = |r1cs| {
+            let circuit = CircomCircuit {
+                r1cs,
+                witness: None,
+                wire_mapping: None,
+                aux_offset: plonk::AUX_OFFSET,
+                constraint_components: None,
+            };
+            plonkit::transpile::transpile_with_gates_count(circuit).expect("transpile err").0
+        };
+        let (before, after) = (num_gates(r1cs), num_gates(optimised.r1cs.clone()));
+        log::info!("gates: {} -> {}", before, after);
+        output::set("num_gates_before", &before);
+        output::set("num_gates_after", &after);
+    }
+    log::info!(
+        "substituted {} linear constraints, merged {} duplicate and dropped {} trivial ones",
+        stats.num_substituted,
+        stats.num_duplicates,
+        stats.num_trivial
+    );
+    output::set("stats", stats);
+
+    if !opts.overwrite {
+        let path = Path::new(&opts.output);
+        assert!(!path.exists(), "duplicate circuit file: {}", path.display());
+        let path = Path::new(&opts.wire_mapping);
+        assert!(!path.exists(), "duplicate wire mapping file: {}", path.display());
+    }
+    let writer = BufWriter::new(File::create(&opts.output).unwrap());
+    serde_json::to_writer(writer, &plonkit::optimise::to_json(&optimised.r1cs)).expect("write failed");
+    log::info!("Optimised circuit saved to {}", opts.output);
+    output::written(&opts.output);
+    let writer = File::create(&opts.wire_mapping).unwrap();
+    serde_json::to_writer(writer, &optimised.wire_mapping).expect("write failed");
+    log::info!("Wire mapping saved to {}", opts.wire_mapping);
+    output::written(&opts.wire_mapping);
+}
+
+// time the proving phases of a circuit, and save the timings to a file
+fn bench(opts: BenchOpts) {
+    let circuit_file = resolve_circuit_file(opts.circuit);
+    log::info!("Loading circuit from {}...", circuit_file);
+    let circuit = CircomCircuit {
+        r1cs: reader::load_r1cs(&circuit_file),
+        witness: Some(reader::load_witness_from_file::<Bn256>(&opts.witness)),
+        wire_mapping: None,
+        aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
+    };
+    let split = |list: &str| list.split(',').map(|s| s.trim().to_owned()).collect();
+    let config = plonkit::bench::BenchConfig {
+        srs_monomial_form: opts.srs_monomial_form,
+        srs_lagrange_form: opts.srs_lagrange_form,
+        srs_forms: split(&opts.srs_forms),
+        transcripts: split(&opts.transcripts),
+        repetitions: opts.repetitions,
+    };
+    let result = plonkit::bench::bench(circuit, &config).expect("bench err");
+    for phase in &result.phases {
+        log::info!(
+            "{}: mean {:.1} ms, min {:.1} ms, max {:.1} ms, peak rss {}",
+            phase.name,
+            phase.mean_ms,
+            phase.min_ms,
+            phase.max_ms,
+            phase.peak_rss_kb.map_or("unknown".to_owned(), |kb| format!("{} KiB", kb))
+        );
+    }
+    for name in &result.skipped {
+        log::warn!("{}: skipped, not supported by the prover", name);
+    }
+
+    let writer = File::create(&opts.output).unwrap();
+    serde_json::to_writer_pretty(writer, &result).expect("write failed");
+    output::set("bench", &result);
+    log::info!("output to {}", opts.output);
+    output::written(&opts.output);
+}
+
+// run the proving steps of a manifest end to end, generating the srs first if it doesn't exist
+fn pipeline(opts: PipelineOpts, manifest: &Manifest) {
+    manifest.create_output_dir().expect("create output dir err");
+    let srs_exists = matches!(&manifest.srs_monomial_form, Some(srs) if Path::new(srs).exists());
+    let steps = ["setup", "export-verification-key", "prove", "verify", "generate-verifier"];
+    for step in steps.iter().filter(|&&step| step != "setup" || !srs_exists) {
+        log::info!("Pipeline step: {}", step);
+        let mut args = vec!["plonkit".to_owned(), step.to_string()];
+        if opts.overwrite && *step != "verify" {
+            args.push("--overwrite".to_owned());
+        }
+        let step_opts = Opts::try_parse_from(manifest.apply(args)).unwrap_or_else(|e| panic!("invalid manifest for {}: {}", step, e));
+        run(step_opts.command, Some(manifest));
+    }
+}
+
+// submit, run or inspect the jobs of a proving queue
+fn queue(opts: QueueOpts, manifest: Option<&Manifest>) {
+    let queue = plonkit::queue::Queue::open(&opts.queue).expect("open queue err");
+    match opts.command {
+        QueueCommand::Submit(o) => {
+            // the job keeps the manifest defaults of the submit time
+            let mut args: Vec<String> = std::iter::once("plonkit".to_owned()).chain(o.args).collect();
+            if let Some(manifest) = manifest {
+                args = manifest.apply(args);
+            }
+            let outputs = match Opts::try_parse_from(args.clone()) {
+                Ok(Opts {
+                    command: SubCommand::Prove(p),
+                }) => vec![p.proof, p.proofjson, p.publicjson],
+                Ok(Opts {
+                    command: SubCommand::RecursiveProve(p),
+                }) => vec![p.new_proof, p.proofjson],
+                Ok(_) => panic!("only prove and recursive-prove can be queued"),
+                Err(e) => panic!("invalid job arguments: {}", e),
+            };
+            let dir = std::env::current_dir().expect("get current dir err");
+            let job = queue.submit(args.split_off(1), dir, outputs).expect("submit job err");
+            log::info!("Job {} queued in {}", job.id, opts.queue);
+            output::set("job", &job.id);
+        }
+        QueueCommand::Run(o) => {
+            // each job runs in its own plonkit process, so that a job exiting or panicking leaves the queue consistent
+            let exe = std::env::current_exe().expect("get plonkit executable err");
+            let summary = queue
+                .run(o.max_attempts, |job| {
+                    let mut args = job.args.clone();
+                    // a retry replaces the outputs of the interrupted attempt
+                    if job.attempts > 1 && !args.iter().any(|a| a == "--overwrite") {
+                        args.push("--overwrite".to_owned());
+                    }
+                    let mut child = std::process::Command::new(&exe).args(&args).current_dir(&job.dir).spawn()?;
+                    if let Err(e) = queue.record_child(job.id, child.id()) {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(e);
+                    }
+                    let status = child.wait()?;
+                    anyhow::ensure!(status.success(), "plonkit {} exited with {}", job.args[0], status);
+                    Ok(())
+                })
+                .expect("run queue err");
+            log::info!(
+                "{} jobs done, {} failed, {} recovered",
+                summary.done.len(),
+                summary.failed.len(),
+                summary.recovered.len()
+            );
+            output::set("done", &summary.done);
+            output::set("failed", &summary.failed);
+            output::set("recovered", &summary.recovered);
+            if !summary.failed.is_empty() {
+                output::fail(output::EXIT_JOBS_FAILED, "some jobs failed");
+            }
+        }
+        QueueCommand::Status(o) => {
+            let jobs = match o.id {
+                Some(id) => vec![queue.get(id).expect("read job err")],
+                None => queue.jobs().expect("read jobs err"),
+            };
+            output::set("jobs", &jobs);
+            if !output::is_json() {
+                println!("{}", serde_json::to_string_pretty(&jobs).unwrap());
+            }
+        }
+    }
+}
+
+// serve proving requests for the circuits until killed
+#[cfg(feature = "serve")]
+fn serve(opts: ServeOpts) {
+    let circuits = match opts.circuit {
+        Some(circuits) => circuits.split(',').map(|c| c.trim().to_owned()).collect(),
+        None => vec![resolve_circuit_file(None)],
+    };
+    let config = plonkit::server::ServerConfig {
+        addr: opts.addr,
+        srs_monomial_form: opts.srs_monomial_form,
+        srs_lagrange_form: opts.srs_lagrange_form,
+        circuits,
+        transcript: opts.transcript,
+    };
+    plonkit::server::serve(config).expect("serve err");
+}
+
+fn estimate_gas(opts: EstimateGasOpts) {
+    let estimate = if opts.recursive {
+        let metadata = match reader::maybe_load_recursive_vk_metadata(&opts.vk) {
+            Ok(Some(metadata)) => metadata,
+            Ok(None) => output::fail(
+                output::EXIT_ERROR,
+                &format!("recursive vk {} has no metadata, export it again to estimate its gas", opts.vk),
+            ),
+            Err(e) => output::fail(output::EXIT_ERROR, &e.to_string()),
+        };
+        plonkit::gas::estimate_recursive_verifier(metadata.num_inputs, metadata.num_proofs_to_check)
+    } else {
+        let vk = reader::load_verification_key::<Bn256>(&opts.vk);
+        plonkit::gas::estimate_single_verifier(vk.num_inputs)
+    };
+    let writer = File::create(&opts.output).unwrap();
+    serde_json::to_writer_pretty(writer, &estimate).expect("write failed");
+    output::set("estimate", &estimate);
+    log::info!(
+        "gas estimate: {}",
+        serde_json::to_string_pretty(&estimate).unwrap_or_else(|_| "<failed>".to_owned())
+    );
+    log::info!("output to {}", opts.output);
+    output::written(&opts.output);
 }
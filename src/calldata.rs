@@ -0,0 +1,207 @@
+// ABI-encode proofs into calldata for the generated solidity verifiers
+use crate::bellman_ce::pairing::bn256::Bn256;
+use crate::bellman_ce::plonk::{better_cs::cs::PlonkCsWidth4WithNextStepParams, better_cs::keys::Proof};
+use crate::recursive::AggregatedProof;
+use crate::utils::{self, BigUint};
+use anyhow::{bail, format_err};
+use num_traits::Num;
+use serde_json::Value;
+use tiny_keccak::{Hasher, Keccak};
+
+// `KeyedVerifier` generated by `generate-verifier`
+const SINGLE_VERIFIER_SIGNATURE: &str = "verify_serialized_proof(uint256[],uint256[])";
+// `KeysWithPlonkVerifier` generated by `generate-recursive-verifier`, the limbs array size is filled in later
+const RECURSIVE_VERIFIER_SIGNATURE: &str = "verifyAggregatedProof(uint256[],uint256[],uint8[],uint256[],uint256[{}])";
+
+// a solidity function argument
+#[derive(Debug, Clone, PartialEq)]
+pub enum AbiParam {
+    // `uint256[]`, `uint8[]` and other dynamic arrays of static words
+    DynamicArray(Vec<BigUint>),
+    // `uint256[N]`
+    FixedArray(Vec<BigUint>),
+}
+
+impl AbiParam {
+    fn words(&self) -> &[BigUint] {
+        match self {
+            AbiParam::DynamicArray(words) | AbiParam::FixedArray(words) => words,
+        }
+    }
+
+    fn is_dynamic(&self) -> bool {
+        matches!(self, AbiParam::DynamicArray(_))
+    }
+}
+
+// ABI-encoded calldata, together with the arguments in json form (e.g. for ethers.js or hardhat)
+pub struct Calldata {
+    pub data: Vec<u8>,
+    pub args: Value,
+}
+
+impl Calldata {
+    pub fn to_hex(&self) -> String {
+        utils::encode_hex(&self.data)
+    }
+}
+
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    let mut hasher = Keccak::v256();
+    hasher.update(signature.as_bytes());
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&hash[..4]);
+    selector
+}
+
+fn encode_word(value: &BigUint) -> Result<[u8; 32], anyhow::Error> {
+    let bytes = value.to_bytes_be();
+    if bytes.len() > 32 {
+        bail!("value {} does not fit into uint256", value);
+    }
+    let mut word = [0u8; 32];
+    word[(32 - bytes.len())..].copy_from_slice(&bytes);
+    Ok(word)
+}
+
+// encode a function call, following the solidity ABI spec
+pub fn encode_function_call(signature: &str, params: &[AbiParam]) -> Result<Vec<u8>, anyhow::Error> {
+    let head_size: usize = params
+        .iter()
+        .map(|p| if p.is_dynamic() { 1 } else { p.words().len() })
+        .sum::<usize>()
+        * 32;
+
+    let mut head = Vec::with_capacity(head_size);
+    let mut tail = Vec::new();
+    for param in params {
+        match param {
+            AbiParam::DynamicArray(words) => {
+                head.extend_from_slice(&encode_word(&BigUint::from(head_size + tail.len()))?);
+                tail.extend_from_slice(&encode_word(&BigUint::from(words.len()))?);
+                for w in words {
+                    tail.extend_from_slice(&encode_word(w)?);
+                }
+            }
+            AbiParam::FixedArray(words) => {
+                for w in words {
+                    head.extend_from_slice(&encode_word(w)?);
+                }
+            }
+        }
+    }
+
+    let mut data = function_selector(signature).to_vec();
+    data.extend(head);
+    data.extend(tail);
+    Ok(data)
+}
+
+// parse a number as serialized by the codegen crates: a "0x" prefixed hex string, a decimal string or a json number
+fn word_from_json(value: &Value) -> Result<BigUint, anyhow::Error> {
+    match value {
+        Value::String(s) if s.starts_with("0x") => Ok(BigUint::from_str_radix(&s[2..], 16)?),
+        Value::String(s) => Ok(BigUint::from_str_radix(s, 10)?),
+        Value::Number(n) => n.as_u64().map(BigUint::from).ok_or_else(|| format_err!("invalid number {}", n)),
+        _ => bail!("unexpected json value {}", value),
+    }
+}
+
+pub(crate) fn words_from_json(value: &Value) -> Result<Vec<BigUint>, anyhow::Error> {
+    value
+        .as_array()
+        .ok_or_else(|| format_err!("expect a json array, got {}", value))?
+        .iter()
+        .map(word_from_json)
+        .collect()
+}
+
+fn words_to_json(words: &[BigUint]) -> Value {
+    Value::Array(words.iter().map(|w| Value::String(w.to_str_radix(10))).collect())
+}
+
+// calldata for `verify_serialized_proof(public_inputs, serialized_proof)` of a single verifier
+pub fn single_verifier_calldata(proof: &Proof<Bn256, PlonkCsWidth4WithNextStepParams>) -> Result<Calldata, anyhow::Error> {
+    let (inputs, serialized_proof) = bellman_vk_codegen::serialize_proof(proof);
+    single_verifier_calldata_from_json(&serde_json::to_value(&inputs)?, &serde_json::to_value(&serialized_proof)?)
+}
+
+// same as `single_verifier_calldata`, from the content of public.json and proof.json written by `prove`
+pub fn single_verifier_calldata_from_json(public_inputs: &Value, serialized_proof: &Value) -> Result<Calldata, anyhow::Error> {
+    let inputs = words_from_json(public_inputs)?;
+    let serialized_proof = words_from_json(serialized_proof)?;
+
+    let data = encode_function_call(
+        SINGLE_VERIFIER_SIGNATURE,
+        &[
+            AbiParam::DynamicArray(inputs.clone()),
+            AbiParam::DynamicArray(serialized_proof.clone()),
+        ],
+    )?;
+    let args = Value::Array(vec![words_to_json(&inputs), words_to_json(&serialized_proof)]);
+    Ok(Calldata { data, args })
+}
+
+// calldata for `verifyAggregatedProof(recursive_input, proof, vk_indexes, individual_vks_inputs, subproofs_limbs)`
+// of a recursive verifier. The arguments come from the json layout of `AggregatedProof`.
+pub fn recursive_verifier_calldata(proof: &AggregatedProof) -> Result<Calldata, anyhow::Error> {
+    recursive_verifier_calldata_from_json(&serde_json::to_value(proof)?)
+}
+
+// same as `recursive_verifier_calldata`, from the content of the proof json written by `recursive-prove`
+pub fn recursive_verifier_calldata_from_json(proof: &Value) -> Result<Calldata, anyhow::Error> {
+    let parts = proof.as_array().ok_or_else(|| format_err!("unexpected aggregated proof layout"))?;
+    if parts.len() != 5 {
+        bail!("unexpected aggregated proof layout, expect 5 parts, got {}", parts.len());
+    }
+    let parts: Vec<Vec<BigUint>> = parts.iter().map(words_from_json).collect::<Result<_, _>>()?;
+    let signature = RECURSIVE_VERIFIER_SIGNATURE.replace("{}", &parts[4].len().to_string());
+
+    let data = encode_function_call(
+        &signature,
+        &[
+            AbiParam::DynamicArray(parts[0].clone()),
+            AbiParam::DynamicArray(parts[1].clone()),
+            AbiParam::DynamicArray(parts[2].clone()),
+            AbiParam::DynamicArray(parts[3].clone()),
+            AbiParam::FixedArray(parts[4].clone()),
+        ],
+    )?;
+    let args = Value::Array(parts.iter().map(|p| words_to_json(p)).collect());
+    Ok(Calldata { data, args })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_selector() {
+        assert_eq!(function_selector("transfer(address,uint256)"), hex!("a9059cbb"));
+    }
+
+    #[test]
+    fn test_encode_function_call() {
+        let data = encode_function_call(
+            "f(uint256[],uint256[2])",
+            &[
+                AbiParam::DynamicArray(vec![BigUint::from(1u64), BigUint::from(2u64)]),
+                AbiParam::FixedArray(vec![BigUint::from(3u64), BigUint::from(4u64)]),
+            ],
+        )
+        .unwrap();
+        let words: Vec<BigUint> = data[4..].chunks(32).map(BigUint::from_bytes_be).collect();
+        let expected: Vec<BigUint> = [0x60u64, 3, 4, 2, 1, 2].iter().map(|w| BigUint::from(*w)).collect();
+        assert_eq!(words, expected);
+    }
+
+    #[test]
+    fn test_word_from_json() {
+        assert_eq!(word_from_json(&Value::String("0x10".to_owned())).unwrap(), BigUint::from(16u64));
+        assert_eq!(word_from_json(&Value::String("10".to_owned())).unwrap(), BigUint::from(10u64));
+        assert_eq!(word_from_json(&serde_json::json!(7)).unwrap(), BigUint::from(7u64));
+        assert!(word_from_json(&Value::Null).is_err());
+    }
+}
@@ -43,7 +43,9 @@ pub struct CircomCircuit<E: Engine> {
     pub witness: Option<Vec<E::Fr>>,
     pub wire_mapping: Option<Vec<usize>>,
     pub aux_offset: usize,
-    // debug symbols
+    // debug symbols: the component path of each constraint, e.g. ["main", "hash[0]"],
+    // synthesized as namespaces so that the gates can be attributed to components
+    pub constraint_components: Option<Vec<Vec<String>>>,
 }
 
 impl<'a, E: Engine> CircomCircuit<E> {
@@ -121,14 +123,58 @@ impl<'a, E: Engine> Circuit<E> for CircomCircuit<E> {
         for (i, constraint) in self.r1cs.constraints.iter().enumerate() {
             // 0 * LC = 0 must be ignored
             if !((constraint.0.is_empty() || constraint.1.is_empty()) && constraint.2.is_empty()) {
+                let component: &[String] = match &self.constraint_components {
+                    Some(components) => &components[i],
+                    None => &[],
+                };
+                for name in component {
+                    cs.push_namespace(|| name.clone());
+                }
                 cs.enforce(
                     || format!("{}", i),
                     |_| make_lc(constraint.0.clone()),
                     |_| make_lc(constraint.1.clone()),
                     |_| make_lc(constraint.2.clone()),
                 );
+                for _ in component {
+                    cs.pop_namespace();
+                }
             }
         }
         Ok(())
     }
 }
+
+// attribute each constraint to the innermost component holding all of its signals,
+// given the signal name of each wire, e.g. `main.hash[0].out`
+pub fn constraint_components<E: Engine>(r1cs: &R1CS<E>, wire_names: &BTreeMap<usize, String>) -> Vec<Vec<String>> {
+    let component_of = |wire: &usize| -> Option<Vec<&str>> {
+        let name = wire_names.get(wire)?;
+        let mut path: Vec<&str> = name.split('.').collect();
+        path.pop();
+        Some(path)
+    };
+    r1cs.constraints
+        .iter()
+        .map(|(a, b, c)| {
+            let mut common: Option<Vec<&str>> = None;
+            // the wire 0 is the constant one, shared by all components
+            for path in a
+                .iter()
+                .chain(b)
+                .chain(c)
+                .filter(|(w, _)| *w != 0)
+                .filter_map(|(w, _)| component_of(w))
+            {
+                common = Some(match common {
+                    None => path,
+                    Some(common) => common.into_iter().zip(path).take_while(|(x, y)| x == y).map(|(x, _)| x).collect(),
+                });
+            }
+            match common {
+                Some(path) if !path.is_empty() => path.into_iter().map(|s| s.to_owned()).collect(),
+                _ => vec!["main".to_owned()],
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,115 @@
+// run the generated solidity verifiers in an embedded EVM, so that they can be checked without a node
+use crate::utils::decode_hex;
+use anyhow::{bail, format_err};
+use revm::db::{CacheDB, EmptyDB};
+use revm::primitives::{Bytes, ExecutionResult, Output, TransactTo};
+use revm::Evm;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+// the verifiers are far more expensive than a usual transaction, give them a whole block
+const GAS_LIMIT: u64 = 30_000_000;
+
+// result of calling a verifier contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvmVerification {
+    // whether the call returned `true`
+    pub valid: bool,
+    // gas used by the call transaction, including the 21000 base cost and calldata
+    pub gas_used: u64,
+}
+
+// compile `contract_name` of a solidity file with the `solc` binary on PATH,
+// using the optimizer settings of the hardhat projects in test/contract
+pub fn compile_contract(sol_file: &str, contract_name: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let output = Command::new("solc")
+        .args(["--optimize", "--optimize-runs", "1000", "--bin", sol_file])
+        .output()
+        .map_err(|e| format_err!("fail to run solc: {}", e))?;
+    if !output.status.success() {
+        bail!("solc failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    // the output looks like "======= verifier.sol:KeyedVerifier =======\nBinary:\n6080..."
+    let stdout = String::from_utf8(output.stdout)?;
+    let header = format!(":{} =======", contract_name);
+    let mut lines = stdout.lines().skip_while(|l| !l.trim_end().ends_with(&header));
+    if lines.next().is_none() {
+        bail!("contract {} not found in {}", contract_name, sol_file);
+    }
+    let bytecode = lines
+        .skip_while(|l| !l.starts_with("Binary"))
+        .nth(1)
+        .ok_or_else(|| format_err!("no bytecode for contract {}", contract_name))?;
+    decode_hex(bytecode)
+}
+
+// load the creation bytecode of a contract: a .sol file is compiled with solc, anything else is read as hex
+pub fn load_bytecode(contract_file: &str, contract_name: &str) -> Result<Vec<u8>, anyhow::Error> {
+    if Path::new(contract_file).extension() == Some(OsStr::new("sol")) {
+        compile_contract(contract_file, contract_name)
+    } else {
+        decode_hex(&std::fs::read_to_string(contract_file)?)
+    }
+}
+
+// deploy a contract from its creation bytecode, then call it with the calldata
+pub fn deploy_and_call(bytecode: &[u8], calldata: &[u8]) -> Result<EvmVerification, anyhow::Error> {
+    let mut evm = Evm::builder()
+        .with_db(CacheDB::new(EmptyDB::default()))
+        // the verifiers can exceed the EIP-170 code size limit
+        .modify_cfg_env(|cfg| cfg.limit_contract_code_size = Some(usize::MAX))
+        .modify_tx_env(|tx| {
+            tx.transact_to = TransactTo::create();
+            tx.data = Bytes::copy_from_slice(bytecode);
+            tx.gas_limit = GAS_LIMIT;
+        })
+        .build();
+    let address = match evm.transact_commit()? {
+        ExecutionResult::Success {
+            output: Output::Create(_, Some(address)),
+            ..
+        } => address,
+        result => bail!("fail to deploy contract: {:?}", result),
+    };
+
+    let tx = evm.tx_mut();
+    tx.transact_to = TransactTo::call(address);
+    tx.data = Bytes::copy_from_slice(calldata);
+    tx.nonce = None;
+    let result = match evm.transact_commit()? {
+        ExecutionResult::Success { output, gas_used, .. } => {
+            let output = output.into_data();
+            // an abi-encoded `bool` is a single word
+            let valid = output.len() == 32 && output[31] == 1 && output[..31].iter().all(|b| *b == 0);
+            EvmVerification { valid, gas_used }
+        }
+        ExecutionResult::Revert { gas_used, .. } | ExecutionResult::Halt { gas_used, .. } => EvmVerification { valid: false, gas_used },
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // creation code of a contract whose runtime code returns `word` for any calldata
+    fn returning_contract(word: u8) -> Vec<u8> {
+        // runtime: PUSH1 word PUSH1 0 MSTORE PUSH1 32 PUSH1 0 RETURN
+        let runtime = [0x60, word, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3];
+        // init: CODECOPY the runtime into memory and RETURN it
+        let len = runtime.len() as u8;
+        let mut code = vec![0x60, len, 0x60, 0x0c, 0x60, 0x00, 0x39, 0x60, len, 0x60, 0x00, 0xf3];
+        code.extend_from_slice(&runtime);
+        code
+    }
+
+    #[test]
+    fn test_deploy_and_call() {
+        let result = deploy_and_call(&returning_contract(1), &[0xde, 0xad, 0xbe, 0xef]).unwrap();
+        assert!(result.valid);
+        assert!(result.gas_used > 21000);
+        assert!(!deploy_and_call(&returning_contract(0), &[]).unwrap().valid);
+    }
+}
@@ -0,0 +1,202 @@
+// estimate the on-chain verification gas of the generated verifier contracts,
+// from the precompile calls they make and the size of their calldata (prices as of the Istanbul/Berlin forks)
+const TX_BASE_GAS: u64 = 21000;
+const CALLDATA_ZERO_BYTE_GAS: u64 = 4;
+const CALLDATA_NONZERO_BYTE_GAS: u64 = 16;
+const EC_ADD_GAS: u64 = 150;
+const EC_MUL_GAS: u64 = 6000;
+const PAIRING_BASE_GAS: u64 = 45000;
+const PAIRING_PER_PAIR_GAS: u64 = 34000;
+const MODEXP_MIN_GAS: u64 = 200;
+
+// number of uint256 words of `serialize_proof` for the single verifier
+const SINGLE_PROOF_WORDS: usize = 33;
+const STATE_WIDTH: usize = 4;
+// ecMul/ecAdd calls of `verify` in contrib/template.sol, the template of the single verifier.
+// reconstruct_d: the selectors of the state (523-526), q_m (531-532) and q_d_next (535-536), then the
+// grand product and last permutation (579-585), where a point_sub_assign is an ecAdd too
+const RECONSTRUCT_D_EC_MUL: usize = STATE_WIDTH + 1 + 1 + 4;
+const RECONSTRUCT_D_EC_ADD: usize = STATE_WIDTH + 1 + 1 + 3;
+// verify_commitments: the quotient chunks (603-606), d (610), the state (612-615) and permutations (618-621),
+// d_next (630-631), the aggregated value (674) and the two opening proofs (677-685)
+const VERIFY_COMMITMENTS_EC_MUL: usize = (STATE_WIDTH - 1) + STATE_WIDTH + (STATE_WIDTH - 1) + 1 + 1 + 3;
+const VERIFY_COMMITMENTS_EC_ADD: usize = (STATE_WIDTH - 1) + 1 + STATE_WIDTH + (STATE_WIDTH - 1) + 1 + 1 + 3;
+const SINGLE_EC_MUL: usize = RECONSTRUCT_D_EC_MUL + VERIFY_COMMITMENTS_EC_MUL;
+const SINGLE_EC_ADD: usize = RECONSTRUCT_D_EC_ADD + VERIFY_COMMITMENTS_EC_ADD;
+// modexp calls with a small exponent besides the lagrange polynomials: z^n in
+// batch_evaluate_lagrange_poly_out_of_domain (388) and evaluate_vanishing (441), v^9 (561) and z^n (595)
+const SMALL_EXPONENT_MODEXPS: usize = 4;
+
+// number of uint256 words of the aggregation circuit proof passed to the recursive verifier
+const RECURSIVE_PROOF_WORDS: usize = 34;
+// the recursive template (in solidity_recursive_plonk_verifier) runs the same verifier, then `verify_recursive`
+// scales the two points reconstructed from the limbs by a challenge and adds them to the two pairing points
+const RECURSIVE_EC_MUL: usize = SINGLE_EC_MUL + 2;
+const RECURSIVE_EC_ADD: usize = SINGLE_EC_ADD + 2;
+// pairing inputs are split into 68-bit limbs
+const RECURSIVE_LIMBS: usize = 16;
+const LIMB_BYTES: usize = 9;
+
+// the verifiers always do a single pairing check with two pairs (pairingProd2, 688)
+const PAIRS: usize = 2;
+
+// gas breakdown of a verification transaction, not counting the plain EVM execution
+// (transcript hashing, field arithmetic and memory), which the contract runs add on top
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct GasEstimate {
+    pub num_inputs: usize,
+    pub calldata_bytes: usize,
+    pub num_ec_add: usize,
+    pub num_ec_mul: usize,
+    pub num_pairs: usize,
+    pub num_modexp: usize,
+    pub tx_base_gas: u64,
+    pub calldata_gas: u64,
+    pub ec_add_gas: u64,
+    pub ec_mul_gas: u64,
+    pub pairing_gas: u64,
+    pub modexp_gas: u64,
+    pub total_gas: u64,
+}
+
+// gas of the modexp precompile (EIP-2565) for 32-byte base and modulus
+fn modexp_gas(exponent_bits: u32) -> u64 {
+    let multiplication_complexity = 16;
+    let iteration_count = exponent_bits.saturating_sub(1).max(1) as u64;
+    (multiplication_complexity * iteration_count / 3).max(MODEXP_MIN_GAS)
+}
+
+// gas of a calldata word with `significant_bytes` non-zero low bytes
+fn word_gas(significant_bytes: usize) -> u64 {
+    significant_bytes as u64 * CALLDATA_NONZERO_BYTE_GAS + (32 - significant_bytes) as u64 * CALLDATA_ZERO_BYTE_GAS
+}
+
+// an abi head (offset) or array length, or a small value like a vk index
+fn small_word_gas() -> u64 {
+    word_gas(1)
+}
+
+fn field_word_gas() -> u64 {
+    word_gas(32)
+}
+
+struct Operations {
+    num_inputs: usize,
+    calldata_bytes: usize,
+    calldata_gas: u64,
+    ec_add: usize,
+    ec_mul: usize,
+    modexp_gas: Vec<u64>,
+}
+
+impl Operations {
+    fn into_estimate(self) -> GasEstimate {
+        let ec_add_gas = self.ec_add as u64 * EC_ADD_GAS;
+        let ec_mul_gas = self.ec_mul as u64 * EC_MUL_GAS;
+        let pairing_gas = PAIRING_BASE_GAS + PAIRS as u64 * PAIRING_PER_PAIR_GAS;
+        let modexp_gas = self.modexp_gas.iter().sum();
+        GasEstimate {
+            num_inputs: self.num_inputs,
+            calldata_bytes: self.calldata_bytes,
+            num_ec_add: self.ec_add,
+            num_ec_mul: self.ec_mul,
+            num_pairs: PAIRS,
+            num_modexp: self.modexp_gas.len(),
+            tx_base_gas: TX_BASE_GAS,
+            calldata_gas: self.calldata_gas,
+            ec_add_gas,
+            ec_mul_gas,
+            pairing_gas,
+            modexp_gas,
+            total_gas: TX_BASE_GAS + self.calldata_gas + ec_add_gas + ec_mul_gas + pairing_gas + modexp_gas,
+        }
+    }
+}
+
+// modexp calls of the verifier: omega^i for the lagrange polynomial of each public input (397) and a single
+// batched inversion (415), which takes a 254-bit exponent, plus the powers by small exponents.
+// Exponents below 2^38 always cost the minimum.
+fn verifier_modexp_gas(num_inputs: usize) -> Vec<u64> {
+    let mut gas = vec![MODEXP_MIN_GAS; num_inputs + SMALL_EXPONENT_MODEXPS];
+    gas.push(modexp_gas(254));
+    gas
+}
+
+// estimate for `verify_serialized_proof(public_inputs, serialized_proof)` of the single verifier
+pub fn estimate_single_verifier(num_inputs: usize) -> GasEstimate {
+    // selector, then heads and lengths of the two arrays
+    let calldata_bytes = 4 + 32 * (4 + num_inputs + SINGLE_PROOF_WORDS);
+    let calldata_gas = 4 * CALLDATA_NONZERO_BYTE_GAS + 4 * small_word_gas() + (num_inputs + SINGLE_PROOF_WORDS) as u64 * field_word_gas();
+    Operations {
+        num_inputs,
+        calldata_bytes,
+        calldata_gas,
+        ec_add: SINGLE_EC_ADD,
+        ec_mul: SINGLE_EC_MUL,
+        modexp_gas: verifier_modexp_gas(num_inputs),
+    }
+    .into_estimate()
+}
+
+// estimate for `verifyAggregatedProof(...)` of the recursive verifier aggregating `num_proofs` proofs,
+// each with `num_inputs` public inputs. The aggregation circuit has a single public input.
+pub fn estimate_recursive_verifier(num_inputs: usize, num_proofs: usize) -> GasEstimate {
+    let individual_inputs = num_inputs * num_proofs;
+    // selector, heads and lengths of the four dynamic arrays, recursive input, proof, vk indexes,
+    // individual inputs and the fixed-size limbs array
+    let calldata_bytes = 4 + 32 * (8 + 1 + RECURSIVE_PROOF_WORDS + num_proofs + individual_inputs + RECURSIVE_LIMBS);
+    let calldata_gas = 4 * CALLDATA_NONZERO_BYTE_GAS
+        + (8 + num_proofs) as u64 * small_word_gas()
+        + (1 + RECURSIVE_PROOF_WORDS + individual_inputs) as u64 * field_word_gas()
+        + RECURSIVE_LIMBS as u64 * word_gas(LIMB_BYTES);
+    Operations {
+        num_inputs: individual_inputs,
+        calldata_bytes,
+        calldata_gas,
+        ec_add: RECURSIVE_EC_ADD,
+        ec_mul: RECURSIVE_EC_MUL,
+        modexp_gas: verifier_modexp_gas(1),
+    }
+    .into_estimate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modexp_gas() {
+        assert_eq!(modexp_gas(254), 16 * 253 / 3);
+        assert_eq!(modexp_gas(20), MODEXP_MIN_GAS);
+    }
+
+    #[test]
+    fn test_estimate_single_verifier() {
+        let estimate = estimate_single_verifier(1);
+        assert_eq!(estimate.calldata_bytes, 4 + 32 * 38);
+        assert_eq!(estimate.num_modexp, 6);
+        assert_eq!(estimate.num_ec_mul, 25);
+        assert_eq!(estimate.num_ec_add, 25);
+        assert_eq!(estimate.pairing_gas, 113000);
+        assert_eq!(
+            estimate.total_gas,
+            estimate.tx_base_gas
+                + estimate.calldata_gas
+                + estimate.ec_add_gas
+                + estimate.ec_mul_gas
+                + estimate.pairing_gas
+                + estimate.modexp_gas
+        );
+        // every public input costs calldata and lagrange evaluation
+        let more_inputs = estimate_single_verifier(2);
+        assert!(more_inputs.total_gas > estimate.total_gas);
+    }
+
+    #[test]
+    fn test_estimate_recursive_verifier() {
+        let estimate = estimate_recursive_verifier(1, 4);
+        assert_eq!(estimate.num_inputs, 4);
+        assert_eq!(estimate.calldata_bytes, 4 + 32 * (8 + 1 + 34 + 4 + 4 + 16));
+        assert_eq!(estimate.num_ec_mul, RECURSIVE_EC_MUL);
+    }
+}
@@ -0,0 +1,324 @@
+// a native PLONK circuit: a list of width-4 gates over numbered variables, proved without the R1CS transpiler.
+// The json format, with coefficients as decimal strings:
+//
+//   {
+//     "num_inputs": 1,
+//     "num_variables": 5,
+//     "gates": [
+//       { "wires": [1, 1, 2, null], "q_m": "1", "q_c": "-1" },
+//       ...
+//     ],
+//     "copy_constraints": [[1, 4]]
+//   }
+//
+// Each gate enforces q_a*a + q_b*b + q_c*c + q_d*d + q_m*a*b + q_const + q_d_next*d_next = 0, where d_next is the
+// d wire of the next gate, a null wire is the dummy variable and a missing selector is zero. The first num_inputs
+// variables are the public inputs. Using a variable in several cells copy-constrains them, and a copy constraint
+// makes two variables the same one, except for two public inputs, which each keep their own input and are tied by
+// an equality gate instead. The witness is a value for each variable.
+use crate::bellman_ce::{
+    pairing::{
+        ff::{Field, PrimeField},
+        Engine,
+    },
+    plonk::better_cs::cs::{Circuit as PlonkCircuit, ConstraintSystem as PlonkConstraintSystem, PlonkCsWidth4WithNextStepParams},
+    SynthesisError,
+};
+use anyhow::format_err;
+
+pub const GATE_CIRCUIT_SUFFIX: &str = ".plonk.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GateJson {
+    pub wires: [Option<usize>; 4],
+    #[serde(default)]
+    pub q_a: Option<String>,
+    #[serde(default)]
+    pub q_b: Option<String>,
+    #[serde(default)]
+    pub q_c: Option<String>,
+    #[serde(default)]
+    pub q_d: Option<String>,
+    #[serde(default)]
+    pub q_m: Option<String>,
+    #[serde(default)]
+    pub q_const: Option<String>,
+    #[serde(default)]
+    pub q_d_next: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GateCircuitJson {
+    pub num_inputs: usize,
+    pub num_variables: usize,
+    pub gates: Vec<GateJson>,
+    #[serde(default)]
+    pub copy_constraints: Vec<[usize; 2]>,
+}
+
+#[derive(Clone)]
+pub struct Gate<E: Engine> {
+    pub wires: [Option<usize>; 4],
+    // q_a, q_b, q_c, q_d, q_m, q_const
+    pub this_step_coeffs: [E::Fr; 6],
+    pub q_d_next: E::Fr,
+}
+
+#[derive(Clone)]
+pub struct GateCircuit<E: Engine> {
+    pub num_inputs: usize,
+    pub num_variables: usize,
+    // the copy constraints are resolved: every wire is the smallest variable it is equal to, and the gates start
+    // with an equality gate for each copy constraint between two public inputs
+    pub gates: Vec<Gate<E>>,
+    pub witness: Option<Vec<E::Fr>>,
+}
+
+pub fn is_gate_circuit_file(filename: &str) -> bool {
+    filename.ends_with(GATE_CIRCUIT_SUFFIX)
+}
+
+// a decimal field element, possibly negative
+fn parse_coeff<F: PrimeField>(s: &Option<String>) -> Result<F, anyhow::Error> {
+    let s = match s {
+        Some(s) => s.trim(),
+        None => return Ok(F::zero()),
+    };
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let mut coeff = F::from_str(digits).ok_or_else(|| format_err!("invalid coefficient {}", s))?;
+    if negative {
+        coeff.negate();
+    }
+    Ok(coeff)
+}
+
+fn find(parent: &mut [usize], v: usize) -> usize {
+    let mut root = v;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    parent[v] = root;
+    root
+}
+
+impl<E: Engine> GateCircuit<E> {
+    pub fn from_json(json: GateCircuitJson) -> Result<Self, anyhow::Error> {
+        anyhow::ensure!(json.num_inputs <= json.num_variables, "more inputs than variables");
+        let check = |v: usize| {
+            anyhow::ensure!(v < json.num_variables, "variable {} out of range", v);
+            Ok(())
+        };
+        let mut parent: Vec<usize> = (0..json.num_variables).collect();
+        let mut gates = Vec::with_capacity(json.gates.len());
+        for [x, y] in &json.copy_constraints {
+            check(*x)?;
+            check(*y)?;
+            let (x, y) = (find(&mut parent, *x), find(&mut parent, *y));
+            if x == y {
+                continue;
+            }
+            // merging two public inputs would leave one of them unconstrained
+            if x < json.num_inputs && y < json.num_inputs {
+                let mut minus_one = E::Fr::one();
+                minus_one.negate();
+                gates.push(Gate {
+                    wires: [Some(x), Some(y), None, None],
+                    this_step_coeffs: [E::Fr::one(), minus_one, E::Fr::zero(), E::Fr::zero(), E::Fr::zero(), E::Fr::zero()],
+                    q_d_next: E::Fr::zero(),
+                });
+                continue;
+            }
+            // the smaller variable is the root, so that a public input stays one
+            parent[std::cmp::max(x, y)] = std::cmp::min(x, y);
+        }
+        for (i, gate) in json.gates.iter().enumerate() {
+            let mut wires = [None; 4];
+            for (wire, v) in wires.iter_mut().zip(gate.wires.iter()) {
+                if let Some(v) = v {
+                    check(*v)?;
+                    *wire = Some(find(&mut parent, *v));
+                }
+            }
+            let coeff = |s: &Option<String>| parse_coeff::<E::Fr>(s).map_err(|e| format_err!("gate {}: {}", i, e));
+            gates.push(Gate {
+                wires,
+                this_step_coeffs: [
+                    coeff(&gate.q_a)?,
+                    coeff(&gate.q_b)?,
+                    coeff(&gate.q_c)?,
+                    coeff(&gate.q_d)?,
+                    coeff(&gate.q_m)?,
+                    coeff(&gate.q_const)?,
+                ],
+                q_d_next: coeff(&gate.q_d_next)?,
+            });
+        }
+        anyhow::ensure!(
+            !matches!(gates.last(), Some(g) if !g.q_d_next.is_zero()),
+            "the last gate has no next gate for q_d_next"
+        );
+        Ok(GateCircuit {
+            num_inputs: json.num_inputs,
+            num_variables: json.num_variables,
+            gates,
+            witness: None,
+        })
+    }
+
+    // a circuit whose public inputs are all zero, so that its proofs can pad an aggregation batch as valid proofs
+    // that aren't mistaken for real ones
+    pub fn dummy(num_inputs: usize) -> Self {
+        let gates = (0..num_inputs)
+            .map(|v| Gate {
+                wires: [Some(v), None, None, None],
+                this_step_coeffs: [
+                    E::Fr::one(),
+                    E::Fr::zero(),
+                    E::Fr::zero(),
+                    E::Fr::zero(),
+                    E::Fr::zero(),
+                    E::Fr::zero(),
+                ],
+                q_d_next: E::Fr::zero(),
+            })
+            .collect();
+        GateCircuit {
+            num_inputs,
+            num_variables: num_inputs,
+            gates,
+            witness: Some(vec![E::Fr::zero(); num_inputs]),
+        }
+    }
+
+    pub fn get_public_inputs(&self) -> Option<Vec<E::Fr>> {
+        self.witness.as_ref().map(|w| w[..self.num_inputs].to_vec())
+    }
+
+    // check the gates against the witness, and name the first unsatisfied one
+    pub fn check_witness(&self) -> Result<(), anyhow::Error> {
+        let witness = self.witness.as_ref().ok_or_else(|| format_err!("no witness"))?;
+        anyhow::ensure!(
+            witness.len() >= self.num_variables,
+            "the witness has {} values for {} variables",
+            witness.len(),
+            self.num_variables
+        );
+        let value = |wire: &Option<usize>| wire.map_or(E::Fr::zero(), |v| witness[v]);
+        for (i, gate) in self.gates.iter().enumerate() {
+            let [q_a, q_b, q_c, q_d, q_m, q_const] = gate.this_step_coeffs;
+            let mut sum = q_const;
+            for (q, wire) in [q_a, q_b, q_c, q_d].iter().zip(gate.wires.iter()) {
+                let mut term = value(wire);
+                term.mul_assign(q);
+                sum.add_assign(&term);
+            }
+            let mut ab = value(&gate.wires[0]);
+            ab.mul_assign(&value(&gate.wires[1]));
+            ab.mul_assign(&q_m);
+            sum.add_assign(&ab);
+            if let Some(next) = self.gates.get(i + 1) {
+                let mut d_next = value(&next.wires[3]);
+                d_next.mul_assign(&gate.q_d_next);
+                sum.add_assign(&d_next);
+            }
+            anyhow::ensure!(sum.is_zero(), "gate {} is not satisfied", i);
+        }
+        Ok(())
+    }
+}
+
+impl<E: Engine> PlonkCircuit<E, PlonkCsWidth4WithNextStepParams> for GateCircuit<E> {
+    fn synthesize<CS: PlonkConstraintSystem<E, PlonkCsWidth4WithNextStepParams>>(&self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let value = |v: usize| match &self.witness {
+            Some(w) => Ok(w[v]),
+            None => Err(SynthesisError::AssignmentMissing),
+        };
+        let mut variables = Vec::with_capacity(self.num_variables);
+        for v in 0..self.num_variables {
+            if v < self.num_inputs {
+                variables.push(cs.alloc_input(|| value(v))?);
+            } else {
+                variables.push(cs.alloc(|| value(v))?);
+            }
+        }
+        let dummy = cs.get_dummy_variable();
+        let variable = |wire: &Option<usize>| wire.map_or(dummy, |v| variables[v]);
+        for gate in &self.gates {
+            cs.new_gate(
+                [
+                    variable(&gate.wires[0]),
+                    variable(&gate.wires[1]),
+                    variable(&gate.wires[2]),
+                    variable(&gate.wires[3]),
+                ],
+                gate.this_step_coeffs,
+                [gate.q_d_next],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bellman_ce::pairing::bn256::{Bn256, Fr};
+
+    #[test]
+    fn test_from_json() {
+        let json: GateCircuitJson = serde_json::from_str(
+            r#"{
+                "num_inputs": 1,
+                "num_variables": 3,
+                "gates": [{ "wires": [2, 2, 0, null], "q_m": "1", "q_c": "-1" }],
+                "copy_constraints": [[1, 2]]
+            }"#,
+        )
+        .unwrap();
+        let mut circuit = GateCircuit::<Bn256>::from_json(json).unwrap();
+        assert_eq!(circuit.gates[0].wires, [Some(1), Some(1), Some(0), None]);
+        let fr = |s: &str| Fr::from_str(s).unwrap();
+        circuit.witness = Some(vec![fr("9"), fr("3"), fr("3")]);
+        assert!(circuit.check_witness().is_ok());
+        circuit.witness = Some(vec![fr("10"), fr("3"), fr("3")]);
+        assert!(circuit.check_witness().is_err());
+        assert!(parse_coeff::<Fr>(&Some("x".to_owned())).is_err());
+    }
+
+    #[test]
+    fn test_copy_constrained_inputs() {
+        let json: GateCircuitJson = serde_json::from_str(
+            r#"{
+                "num_inputs": 2,
+                "num_variables": 3,
+                "gates": [{ "wires": [1, 2, null, null], "q_a": "1", "q_b": "-1" }],
+                "copy_constraints": [[0, 1]]
+            }"#,
+        )
+        .unwrap();
+        let mut circuit = GateCircuit::<Bn256>::from_json(json).unwrap();
+        // both inputs stay, tied by an equality gate
+        assert_eq!(circuit.gates.len(), 2);
+        assert_eq!(circuit.gates[0].wires, [Some(0), Some(1), None, None]);
+        assert_eq!(circuit.gates[1].wires, [Some(1), Some(2), None, None]);
+        let fr = |s: &str| Fr::from_str(s).unwrap();
+        circuit.witness = Some(vec![fr("3"), fr("3"), fr("3")]);
+        assert!(circuit.check_witness().is_ok());
+        circuit.witness = Some(vec![fr("4"), fr("3"), fr("3")]);
+        assert!(circuit.check_witness().is_err());
+    }
+
+    #[test]
+    fn test_dummy() {
+        let mut circuit = GateCircuit::<Bn256>::dummy(2);
+        assert_eq!(circuit.get_public_inputs(), Some(vec![Fr::zero(); 2]));
+        assert!(circuit.check_witness().is_ok());
+        circuit.witness = Some(vec![Fr::zero(), Fr::one()]);
+        assert!(circuit.check_witness().is_err());
+    }
+}
@@ -1,26 +1,74 @@
 #![allow(clippy::unit_arg)]
+#![cfg_attr(not(feature = "prover"), no_std)]
 
+#[cfg(any(test, feature = "verifier"))]
+extern crate alloc;
+#[cfg(feature = "prover")]
 #[macro_use]
 extern crate serde;
+#[cfg(feature = "prover")]
 #[macro_use]
 extern crate hex_literal;
+#[cfg(feature = "prover")]
 extern crate bellman_vk_codegen;
+#[cfg(feature = "prover")]
 extern crate byteorder;
+#[cfg(feature = "prover")]
 extern crate franklin_crypto;
+#[cfg(feature = "prover")]
 extern crate itertools;
+#[cfg(feature = "prover")]
 extern crate num_bigint;
+#[cfg(feature = "prover")]
 extern crate num_traits;
+#[cfg(feature = "prover")]
 extern crate rand;
+#[cfg(feature = "prover")]
+extern crate tiny_keccak;
+#[cfg(feature = "prover")]
+extern crate toml;
 
+#[cfg(feature = "prover")]
+pub mod bench;
+#[cfg(feature = "prover")]
+pub mod calldata;
+#[cfg(feature = "prover")]
 pub mod circom_circuit;
+#[cfg(feature = "evm")]
+pub mod evm;
+#[cfg(feature = "prover")]
+pub mod gas;
+#[cfg(feature = "prover")]
+pub mod gate_circuit;
+#[cfg(feature = "prover")]
+pub mod manifest;
+#[cfg(feature = "prover")]
+pub mod optimise;
+#[cfg(feature = "prover")]
+pub mod output;
+#[cfg(feature = "prover")]
 pub mod plonk;
+#[cfg(feature = "prover")]
+pub mod queue;
+#[cfg(feature = "prover")]
 pub mod r1cs_file;
+#[cfg(feature = "prover")]
 pub mod reader;
+#[cfg(feature = "prover")]
 pub mod recursive;
+#[cfg(feature = "prover")]
+pub mod rust_verifier;
+#[cfg(feature = "serve")]
+pub mod server;
+#[cfg(feature = "prover")]
 pub mod transpile;
+#[cfg(feature = "prover")]
 pub mod utils;
+#[cfg(feature = "verifier")]
+pub mod verifier;
 
+#[cfg(feature = "prover")]
 pub use franklin_crypto::bellman as bellman_ce;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "prover"))]
 mod tests;
@@ -0,0 +1,491 @@
+// a `plonkit.toml` project manifest, declaring the files and parameters shared by the subcommands:
+//
+//   circuit = "circuit.r1cs"
+//   witness = "witness.wtns"
+//   srs_monomial_form = "keys/setup_2^20.key"
+//   srs_power = 20
+//   transcript = "keccak"
+//   output_dir = "build"
+//
+//   [recursive]
+//   srs_monomial_form = "keys/setup_2^24.key"
+//   old_proof_list = "old_proof_list.txt"
+//   num_proofs_to_check = 4
+//   num_inputs = 1
+//
+// The manifest provides defaults: they are added to the command line arguments unless the option is given explicitly.
+use crate::gate_circuit::is_gate_circuit_file;
+use anyhow::format_err;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "plonkit.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub circuit: Option<String>,
+    pub witness: Option<String>,
+    pub srs_monomial_form: Option<String>,
+    pub srs_lagrange_form: Option<String>,
+    // power of two of the srs generated by `setup`
+    pub srs_power: Option<u32>,
+    pub transcript: Option<String>,
+    // directory of the generated keys, proofs and verifiers, the working directory by default
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub recursive: RecursiveManifest,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecursiveManifest {
+    pub srs_monomial_form: Option<String>,
+    pub old_proof_list: Option<String>,
+    pub old_vk_list: Option<String>,
+    pub num_proofs_to_check: Option<usize>,
+    pub num_inputs: Option<usize>,
+    pub vk_tree_depth: Option<usize>,
+}
+
+// an option of a subcommand, set from the manifest
+#[derive(Debug, Clone, PartialEq)]
+struct ManifestArg {
+    short: Option<char>,
+    long: &'static str,
+    value: String,
+}
+
+impl ManifestArg {
+    // whether a command line argument sets this option: `--long`, `--long=value`, `-s` or `-s=value`
+    fn is_given_by(&self, arg: &str) -> bool {
+        let given_as = |flag: &str| arg == flag || arg.strip_prefix(flag).map_or(false, |rest| rest.starts_with('='));
+        given_as(&format!("--{}", self.long)) || matches!(self.short, Some(c) if given_as(&format!("-{}", c)))
+    }
+
+    // the value of this option in the command line arguments, if it is given
+    fn value_in<'a>(&self, args: &'a [String]) -> Option<&'a str> {
+        let pos = args.iter().position(|a| self.is_given_by(a))?;
+        match args[pos].split_once('=') {
+            Some((_, value)) => Some(value),
+            None => args.get(pos + 1).map(String::as_str),
+        }
+    }
+}
+
+// how the command line affects the defaults of a subcommand
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Invocation {
+    // whether it is for an aggregated proof (its `--recursive` flag)
+    recursive: bool,
+    // whether its circuit is a native PLONK one, which is proved without a lagrange form srs
+    native_circuit: bool,
+}
+
+impl Manifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        toml::from_str(&content).map_err(|e| format_err!("invalid manifest {}: {}", path.as_ref().display(), e))
+    }
+
+    // the manifest of the working directory, or the one named by the PLONKIT_MANIFEST env var
+    pub fn maybe_load() -> Result<Option<Self>, anyhow::Error> {
+        let path = std::env::var("PLONKIT_MANIFEST").unwrap_or_else(|_| MANIFEST_FILE.to_owned());
+        if Path::new(&path).exists() {
+            Self::load(path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // path of a generated file in the output directory
+    pub fn output(&self, filename: &str) -> String {
+        match &self.output_dir {
+            Some(dir) => PathBuf::from(dir).join(filename).to_string_lossy().into_owned(),
+            None => filename.to_owned(),
+        }
+    }
+
+    // the defaults of a subcommand
+    fn args_for(&self, subcommand: &str, invocation: Invocation) -> Vec<ManifestArg> {
+        let Invocation { recursive, native_circuit } = invocation;
+        let r = &self.recursive;
+        let out = |f: &str| Some(self.output(f));
+        let out_or_recursive = |f: &str| Some(self.output(&if recursive { format!("recursive_{}", f) } else { f.to_owned() }));
+        let opts: Vec<(Option<char>, &'static str, Option<String>)> = match subcommand {
+            "analyse" => vec![
+                (Some('c'), "circuit", self.circuit.clone()),
+                (Some('o'), "output", out("analyse.json")),
+            ],
+            "setup" => vec![
+                (Some('p'), "power", self.srs_power.map(|p| p.to_string())),
+                (Some('m'), "srs_monomial_form", self.srs_monomial_form.clone()),
+            ],
+            "dump-lagrange" => vec![
+                (Some('m'), "srs_monomial_form", self.srs_monomial_form.clone()),
+                (Some('l'), "srs_lagrange_form", self.srs_lagrange_form.clone()),
+                (Some('c'), "circuit", self.circuit.clone()),
+            ],
+            "prove" => vec![
+                (Some('m'), "srs_monomial_form", self.srs_monomial_form.clone()),
+                (
+                    Some('l'),
+                    "srs_lagrange_form",
+                    self.srs_lagrange_form.clone().filter(|_| !native_circuit),
+                ),
+                (Some('c'), "circuit", self.circuit.clone()),
+                (Some('w'), "witness", self.witness.clone()),
+                (Some('p'), "proof", out("proof.bin")),
+                (Some('j'), "proofjson", out("proof.json")),
+                (Some('i'), "publicjson", out("public.json")),
+                (Some('t'), "transcript", self.transcript.clone()),
+            ],
+            "bench" => vec![
+                (Some('m'), "srs_monomial_form", self.srs_monomial_form.clone()),
+                (Some('l'), "srs_lagrange_form", self.srs_lagrange_form.clone()),
+                (Some('c'), "circuit", self.circuit.clone()),
+                (Some('w'), "witness", self.witness.clone()),
+                (Some('o'), "output", out("bench.json")),
+            ],
+            "verify" => vec![
+                (Some('p'), "proof", out("proof.bin")),
+                (Some('v'), "verification_key", out("vk.bin")),
+                (Some('t'), "transcript", self.transcript.clone()),
+            ],
+            "generate-verifier" => vec![
+                (Some('v'), "verification_key", out("vk.bin")),
+                (Some('s'), "sol", out("verifier.sol")),
+            ],
+            "generate-rust-verifier" => vec![
+                (Some('v'), "verification_key", out("vk.bin")),
+                (Some('o'), "output", out("verifier")),
+            ],
+            "export-verification-key" => vec![
+                (Some('m'), "srs_monomial_form", self.srs_monomial_form.clone()),
+                (Some('c'), "circuit", self.circuit.clone()),
+                (Some('v'), "vk", out("vk.bin")),
+            ],
+            "export-recursive-proving-key" => vec![
+                (Some('c'), "num_proofs_to_check", r.num_proofs_to_check.map(|n| n.to_string())),
+                (Some('i'), "num_inputs", r.num_inputs.map(|n| n.to_string())),
+                (Some('d'), "vk_tree_depth", r.vk_tree_depth.map(|d| d.to_string())),
+                (Some('p'), "pk", out("recursive_pk.bin")),
+            ],
+            "export-recursive-verification-key" => vec![
+                (Some('c'), "num_proofs_to_check", r.num_proofs_to_check.map(|n| n.to_string())),
+                (Some('i'), "num_inputs", r.num_inputs.map(|n| n.to_string())),
+                (Some('m'), "srs_monomial_form", r.srs_monomial_form.clone()),
+                (Some('v'), "vk", out("recursive_vk.bin")),
+                (Some('d'), "vk_tree_depth", r.vk_tree_depth.map(|d| d.to_string())),
+            ],
+            "generate-recursive-verifier" => vec![
+                (Some('o'), "old_vk", out("vk.bin")),
+                (Some('l'), "old_vk_list", r.old_vk_list.clone()),
+                (Some('d'), "vk_tree_depth", r.vk_tree_depth.map(|d| d.to_string())),
+                (Some('n'), "new_vk", out("recursive_vk.bin")),
+                (Some('i'), "num_inputs", r.num_inputs.map(|n| n.to_string())),
+                (Some('s'), "sol", out("recursive_verifier.sol")),
+            ],
+            "recursive-prove" => vec![
+                (Some('m'), "srs_monomial_form", r.srs_monomial_form.clone()),
+                (Some('f'), "old_proof_list", r.old_proof_list.clone()),
+                (Some('v'), "old_vk", out("vk.bin")),
+                (Some('l'), "old_vk_list", r.old_vk_list.clone()),
+                (Some('d'), "vk_tree_depth", r.vk_tree_depth.map(|d| d.to_string())),
+                (Some('n'), "new_proof", out("recursive_proof.bin")),
+                (Some('j'), "proofjson", out("recursive_proof.json")),
+            ],
+            "recursive-verify" => vec![
+                (Some('p'), "proof", out("recursive_proof.bin")),
+                (Some('v'), "verification_key", out("recursive_vk.bin")),
+            ],
+            "check-aggregation" => vec![
+                (Some('o'), "old_proof_list", r.old_proof_list.clone()),
+                (Some('v'), "old_vk", out("vk.bin")),
+                (Some('l'), "old_vk_list", r.old_vk_list.clone()),
+                (Some('d'), "vk_tree_depth", r.vk_tree_depth.map(|d| d.to_string())),
+                (Some('n'), "new_proof", out("recursive_proof.bin")),
+            ],
+            "calldata" => vec![
+                (Some('p'), "proof", out_or_recursive("proof.bin")),
+                (Some('o'), "output", out("calldata.hex")),
+                (Some('j'), "argsjson", out("calldata.json")),
+            ],
+            "estimate-gas" => vec![
+                (Some('v'), "verification_key", out_or_recursive("vk.bin")),
+                (Some('o'), "output", out("gas.json")),
+            ],
+            "verify-evm" => vec![
+                (Some('c'), "contract", out_or_recursive("verifier.sol")),
+                (Some('j'), "proofjson", out_or_recursive("proof.json")),
+                (Some('i'), "publicjson", if recursive { None } else { out("public.json") }),
+            ],
+            "optimise-r1cs" => vec![
+                (Some('c'), "circuit", self.circuit.clone()),
+                (Some('o'), "output", out("circuit_optimised.r1cs.json")),
+                (Some('m'), "wire_mapping", out("wire_mapping.json")),
+            ],
+            "serve" => vec![
+                (Some('c'), "circuit", self.circuit.clone()),
+                (Some('m'), "srs_monomial_form", self.srs_monomial_form.clone()),
+                (Some('l'), "srs_lagrange_form", self.srs_lagrange_form.clone()),
+                (Some('t'), "transcript", self.transcript.clone()),
+            ],
+            _ => vec![],
+        };
+        opts.into_iter()
+            .filter_map(|(short, long, value)| value.map(|value| ManifestArg { short, long, value }))
+            .collect()
+    }
+
+    // add the manifest defaults to the command line arguments `[plonkit, subcommand, options...]`,
+    // global flags may come before the subcommand
+    pub fn apply(&self, args: Vec<String>) -> Vec<String> {
+        let pos = match args.iter().skip(1).position(|a| !a.starts_with('-')) {
+            Some(pos) => pos + 1,
+            None => return args,
+        };
+        let options = &args[pos + 1..];
+        let circuit = ManifestArg {
+            short: Some('c'),
+            long: "circuit",
+            value: String::new(),
+        };
+        let invocation = Invocation {
+            recursive: options.iter().any(|a| a == "-r" || a == "--recursive"),
+            native_circuit: circuit
+                .value_in(options)
+                .or(self.circuit.as_deref())
+                .map_or(false, is_gate_circuit_file),
+        };
+        let defaults: Vec<String> = self
+            .args_for(&args[pos], invocation)
+            .into_iter()
+            .filter(|arg| !options.iter().any(|a| arg.is_given_by(a)))
+            .flat_map(|arg| vec![format!("--{}", arg.long), arg.value])
+            .collect();
+        let mut result = args[..=pos].to_vec();
+        result.extend(defaults);
+        result.extend_from_slice(&args[pos + 1..]);
+        result
+    }
+
+    pub fn create_output_dir(&self) -> Result<(), anyhow::Error> {
+        if let Some(dir) = &self.output_dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_apply() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            circuit = "circuit.r1cs"
+            srs_monomial_form = "setup_2^10.key"
+            output_dir = "build"
+            [recursive]
+            num_inputs = 3
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.recursive.num_inputs, Some(3));
+
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "export-verification-key", "-c", "other.r1cs", "--overwrite"])),
+            args(&[
+                "plonkit",
+                "export-verification-key",
+                "--srs_monomial_form",
+                "setup_2^10.key",
+                "--vk",
+                "build/vk.bin",
+                "-c",
+                "other.r1cs",
+                "--overwrite"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "verify", "--verification_key=vk.bin"])),
+            args(&["plonkit", "verify", "--proof", "build/proof.bin", "--verification_key=vk.bin"])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "--json", "analyse"])),
+            args(&[
+                "plonkit",
+                "--json",
+                "analyse",
+                "--circuit",
+                "circuit.r1cs",
+                "--output",
+                "build/analyse.json"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "queue", "status"])),
+            args(&["plonkit", "queue", "status"])
+        );
+        assert!(toml::from_str::<Manifest>("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_apply_native_circuit() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            srs_monomial_form = "setup_2^10.key"
+            srs_lagrange_form = "setup_2^10.lag.key"
+            "#,
+        )
+        .unwrap();
+        // a native circuit is proved without the lagrange form srs
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "prove", "-c=circuit.plonk.json"])),
+            args(&[
+                "plonkit",
+                "prove",
+                "--srs_monomial_form",
+                "setup_2^10.key",
+                "--proof",
+                "proof.bin",
+                "--proofjson",
+                "proof.json",
+                "--publicjson",
+                "public.json",
+                "-c=circuit.plonk.json"
+            ])
+        );
+        let manifest = Manifest {
+            circuit: Some("circuit.r1cs".to_owned()),
+            ..manifest
+        };
+        // only the exact short option is given, the value `-circuit.json` of `-o` is not `-c`
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "analyse", "-o", "-circuit.json"])),
+            args(&["plonkit", "analyse", "--circuit", "circuit.r1cs", "-o", "-circuit.json"])
+        );
+        assert!(manifest
+            .apply(args(&["plonkit", "prove"]))
+            .contains(&"--srs_lagrange_form".to_owned()));
+        let manifest = Manifest {
+            circuit: Some("circuit.plonk.json".to_owned()),
+            ..manifest
+        };
+        assert!(!manifest
+            .apply(args(&["plonkit", "prove"]))
+            .contains(&"--srs_lagrange_form".to_owned()));
+    }
+
+    #[test]
+    fn test_apply_to_generated_files() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            circuit = "circuit.r1cs"
+            srs_monomial_form = "setup_2^10.key"
+            transcript = "rescue"
+            output_dir = "build"
+            "#,
+        )
+        .unwrap();
+        // after a pipeline, the proof is in the output directory
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "calldata"])),
+            args(&[
+                "plonkit",
+                "calldata",
+                "--proof",
+                "build/proof.bin",
+                "--output",
+                "build/calldata.hex",
+                "--argsjson",
+                "build/calldata.json"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "calldata", "-r"])),
+            args(&[
+                "plonkit",
+                "calldata",
+                "--proof",
+                "build/recursive_proof.bin",
+                "--output",
+                "build/calldata.hex",
+                "--argsjson",
+                "build/calldata.json",
+                "-r"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "estimate-gas", "--recursive"])),
+            args(&[
+                "plonkit",
+                "estimate-gas",
+                "--verification_key",
+                "build/recursive_vk.bin",
+                "--output",
+                "build/gas.json",
+                "--recursive"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "verify-evm"])),
+            args(&[
+                "plonkit",
+                "verify-evm",
+                "--contract",
+                "build/verifier.sol",
+                "--proofjson",
+                "build/proof.json",
+                "--publicjson",
+                "build/public.json"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "verify-evm", "-r"])),
+            args(&[
+                "plonkit",
+                "verify-evm",
+                "--contract",
+                "build/recursive_verifier.sol",
+                "--proofjson",
+                "build/recursive_proof.json",
+                "-r"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "optimise-r1cs", "-m", "mapping.json"])),
+            args(&[
+                "plonkit",
+                "optimise-r1cs",
+                "--circuit",
+                "circuit.r1cs",
+                "--output",
+                "build/circuit_optimised.r1cs.json",
+                "-m",
+                "mapping.json"
+            ])
+        );
+        assert_eq!(
+            manifest.apply(args(&["plonkit", "serve", "-a", "127.0.0.1:9000"])),
+            args(&[
+                "plonkit",
+                "serve",
+                "--circuit",
+                "circuit.r1cs",
+                "--srs_monomial_form",
+                "setup_2^10.key",
+                "--transcript",
+                "rescue",
+                "-a",
+                "127.0.0.1:9000"
+            ])
+        );
+    }
+}
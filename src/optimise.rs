@@ -0,0 +1,295 @@
+// an optimisation pass over an R1CS before it is transpiled: linear constraints are substituted out, duplicate
+// constraints merged, unused wires dropped and the remaining wires renumbered.
+// The public inputs keep their wires, and the wire mapping gives the wire of the original witness for each new wire,
+// so the witness of the original circuit proves the optimised one.
+use crate::bellman_ce::pairing::{
+    ff::{Field, PrimeField},
+    Engine,
+};
+use crate::circom_circuit::{CircuitJson, Constraint, R1CS};
+use crate::utils::repr_to_big;
+use std::collections::{BTreeMap, BTreeSet};
+
+type Lc<E> = Vec<(usize, <E as crate::bellman_ce::pairing::ff::ScalarEngine>::Fr)>;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OptimiseStats {
+    pub num_constraints_before: usize,
+    pub num_constraints_after: usize,
+    pub num_variables_before: usize,
+    pub num_variables_after: usize,
+    pub num_substituted: usize,
+    pub num_duplicates: usize,
+    // constraints left as 0 = 0
+    pub num_trivial: usize,
+}
+
+pub struct Optimised<E: Engine> {
+    pub r1cs: R1CS<E>,
+    pub wire_mapping: Vec<usize>,
+    pub stats: OptimiseStats,
+}
+
+// merge the terms of the same wire, and drop the zero ones
+fn normalize<E: Engine, I: IntoIterator<Item = (usize, E::Fr)>>(terms: I) -> Lc<E> {
+    let mut merged: BTreeMap<usize, E::Fr> = BTreeMap::new();
+    for (wire, coeff) in terms {
+        merged.entry(wire).or_insert_with(E::Fr::zero).add_assign(&coeff);
+    }
+    merged.into_iter().filter(|(_, coeff)| !coeff.is_zero()).collect()
+}
+
+fn scaled<E: Engine>(lc: &[(usize, E::Fr)], k: &E::Fr) -> Vec<(usize, E::Fr)> {
+    lc.iter()
+        .map(|(wire, coeff)| {
+            let mut coeff = *coeff;
+            coeff.mul_assign(k);
+            (*wire, coeff)
+        })
+        .collect()
+}
+
+// the value of a linear combination of the constant wire only
+fn as_constant<E: Engine>(lc: &[(usize, E::Fr)]) -> Option<E::Fr> {
+    match lc {
+        [] => Some(E::Fr::zero()),
+        [(0, coeff)] => Some(*coeff),
+        _ => None,
+    }
+}
+
+// a constraint `k * B = C` or `A * k = C` as the linear combination `k * B - C` equal to zero
+fn as_linear<E: Engine>((a, b, c): &Constraint<E>) -> Option<Lc<E>> {
+    let (k, lc) = match (as_constant::<E>(a), as_constant::<E>(b)) {
+        (Some(k), _) => (k, b),
+        (None, Some(k)) => (k, a),
+        (None, None) => return None,
+    };
+    let mut minus_one = E::Fr::one();
+    minus_one.negate();
+    Some(normalize::<E, _>(scaled::<E>(lc, &k).into_iter().chain(scaled::<E>(c, &minus_one))))
+}
+
+// replace `wire` with the linear combination `replacement`
+fn substitute<E: Engine>(lc: &[(usize, E::Fr)], wire: usize, replacement: &[(usize, E::Fr)]) -> Lc<E> {
+    match lc.iter().find(|(w, _)| *w == wire) {
+        Some((_, k)) => normalize::<E, _>(lc.iter().filter(|(w, _)| *w != wire).cloned().chain(scaled::<E>(replacement, k))),
+        None => lc.to_vec(),
+    }
+}
+
+fn wires<E: Engine>((a, b, c): &Constraint<E>) -> impl Iterator<Item = usize> + '_ {
+    a.iter().chain(b).chain(c).map(|(w, _)| *w)
+}
+
+// a key equal for the same constraint, up to the order of A and B
+fn constraint_key<E: Engine>((a, b, c): &Constraint<E>) -> Vec<Vec<(usize, <E::Fr as PrimeField>::Repr)>> {
+    let key = |lc: &Lc<E>| lc.iter().map(|(w, coeff)| (*w, coeff.into_repr())).collect::<Vec<_>>();
+    let (a, b) = (key(a), key(b));
+    let (a, b) = if a <= b { (a, b) } else { (b, a) };
+    vec![a, b, key(c)]
+}
+
+pub fn optimise<E: Engine>(r1cs: &R1CS<E>) -> Optimised<E> {
+    let num_inputs = r1cs.num_inputs;
+    let mut stats = OptimiseStats {
+        num_constraints_before: r1cs.constraints.len(),
+        num_variables_before: r1cs.num_variables,
+        ..Default::default()
+    };
+    let mut constraints: Vec<Option<Constraint<E>>> = r1cs
+        .constraints
+        .iter()
+        .map(|(a, b, c)| {
+            Some((
+                normalize::<E, _>(a.iter().cloned()),
+                normalize::<E, _>(b.iter().cloned()),
+                normalize::<E, _>(c.iter().cloned()),
+            ))
+        })
+        .collect();
+    let mut occurrences: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        for wire in wires::<E>(constraint.as_ref().unwrap()) {
+            occurrences.entry(wire).or_default().insert(i);
+        }
+    }
+
+    // substituting a linear combination of n terms for a wire adds about n - 2 terms to each constraint using it,
+    // so a wire is substituted only if it doesn't add more terms than the linear constraint removes
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 0..constraints.len() {
+            let lc = match constraints[i].as_ref().and_then(as_linear::<E>) {
+                Some(lc) => lc,
+                None => continue,
+            };
+            let constraint = constraints[i].take().unwrap();
+            for wire in wires::<E>(&constraint) {
+                occurrences.get_mut(&wire).unwrap().remove(&i);
+            }
+            if lc.is_empty() {
+                stats.num_trivial += 1;
+                continue;
+            }
+            // the private wire used by the fewest constraints
+            let candidate = lc
+                .iter()
+                .filter(|(w, _)| *w >= num_inputs)
+                .min_by_key(|(w, _)| occurrences[w].len())
+                .cloned();
+            let (wire, coeff) = match candidate {
+                Some((wire, coeff)) if occurrences[&wire].len() * lc.len().saturating_sub(2) <= lc.len() => (wire, coeff),
+                _ => {
+                    for w in wires::<E>(&constraint) {
+                        occurrences.get_mut(&w).unwrap().insert(i);
+                    }
+                    constraints[i] = Some(constraint);
+                    continue;
+                }
+            };
+            // wire = -1/coeff * (lc - coeff * wire)
+            let mut k = coeff.inverse().unwrap();
+            k.negate();
+            let rest: Vec<(usize, E::Fr)> = lc.iter().filter(|(w, _)| *w != wire).cloned().collect();
+            let replacement = scaled::<E>(&rest, &k);
+            for j in occurrences.remove(&wire).unwrap() {
+                let (a, b, c) = constraints[j].as_ref().unwrap();
+                let substituted = (
+                    substitute::<E>(a, wire, &replacement),
+                    substitute::<E>(b, wire, &replacement),
+                    substitute::<E>(c, wire, &replacement),
+                );
+                for w in wires::<E>(&substituted) {
+                    occurrences.entry(w).or_default().insert(j);
+                }
+                constraints[j] = Some(substituted);
+            }
+            stats.num_substituted += 1;
+            changed = true;
+        }
+    }
+
+    let mut seen = BTreeSet::new();
+    let mut remaining = vec![];
+    for constraint in constraints.into_iter().flatten() {
+        if !seen.insert(constraint_key::<E>(&constraint)) {
+            stats.num_duplicates += 1;
+            continue;
+        }
+        remaining.push(constraint);
+    }
+
+    // the public inputs keep their wires, the used private wires follow in their original order
+    let used: BTreeSet<usize> = remaining.iter().flat_map(wires::<E>).filter(|w| *w >= num_inputs).collect();
+    let wire_mapping: Vec<usize> = (0..num_inputs).chain(used).collect();
+    let new_wires: BTreeMap<usize, usize> = wire_mapping.iter().enumerate().map(|(new, old)| (*old, new)).collect();
+    let renumber = |lc: Lc<E>| -> Lc<E> { lc.into_iter().map(|(w, coeff)| (new_wires[&w], coeff)).collect() };
+    let constraints: Vec<Constraint<E>> = remaining
+        .into_iter()
+        .map(|(a, b, c)| (renumber(a), renumber(b), renumber(c)))
+        .collect();
+
+    stats.num_constraints_after = constraints.len();
+    stats.num_variables_after = wire_mapping.len();
+    Optimised {
+        r1cs: R1CS {
+            num_inputs,
+            num_aux: wire_mapping.len() - num_inputs,
+            num_variables: wire_mapping.len(),
+            constraints,
+        },
+        wire_mapping,
+        stats,
+    }
+}
+
+// the circuit in the json format of `snarkjs r1cs export json`, with all the public signals as inputs
+pub fn to_json<E: Engine>(r1cs: &R1CS<E>) -> CircuitJson {
+    let lc_json = |lc: &Lc<E>| -> BTreeMap<String, String> {
+        lc.iter()
+            .map(|(w, coeff)| (w.to_string(), repr_to_big(coeff.into_repr())))
+            .collect()
+    };
+    CircuitJson {
+        constraints: r1cs
+            .constraints
+            .iter()
+            .map(|(a, b, c)| vec![lc_json(a), lc_json(b), lc_json(c)])
+            .collect(),
+        num_inputs: r1cs.num_inputs - 1,
+        num_outputs: 0,
+        num_variables: r1cs.num_variables,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bellman_ce::pairing::bn256::{Bn256, Fr};
+
+    fn fr(n: u64) -> Fr {
+        Fr::from_str(&n.to_string()).unwrap()
+    }
+
+    fn lc(terms: &[(usize, u64)]) -> Lc<Bn256> {
+        terms.iter().map(|(w, n)| (*w, fr(*n))).collect()
+    }
+
+    fn is_satisfied(r1cs: &R1CS<Bn256>, witness: &[Fr]) -> bool {
+        let eval = |lc: &Lc<Bn256>| {
+            let mut sum = Fr::zero();
+            for (w, coeff) in lc {
+                let mut term = witness[*w];
+                term.mul_assign(coeff);
+                sum.add_assign(&term);
+            }
+            sum
+        };
+        r1cs.constraints.iter().all(|(a, b, c)| {
+            let mut ab = eval(a);
+            ab.mul_assign(&eval(b));
+            ab == eval(c)
+        })
+    }
+
+    #[test]
+    fn test_optimise() {
+        // wires: one, out, a, b, c, unused
+        let r1cs = R1CS::<Bn256> {
+            num_inputs: 2,
+            num_aux: 4,
+            num_variables: 6,
+            constraints: vec![
+                (lc(&[(2, 1)]), lc(&[(2, 1)]), lc(&[(3, 1)])),
+                // c = b, substituted out
+                (lc(&[(0, 1)]), lc(&[(3, 1)]), lc(&[(4, 1)])),
+                (lc(&[(4, 1)]), lc(&[(2, 1)]), lc(&[(1, 1)])),
+                // the first constraint again
+                (lc(&[(2, 1)]), lc(&[(2, 1)]), lc(&[(3, 1)])),
+                (lc(&[(2, 1)]), lc(&[]), lc(&[])),
+            ],
+        };
+        let witness: Vec<Fr> = [1, 27, 3, 9, 9, 0].iter().map(|n| fr(*n)).collect();
+        assert!(is_satisfied(&r1cs, &witness));
+
+        let optimised = optimise(&r1cs);
+        assert_eq!(
+            optimised.stats,
+            OptimiseStats {
+                num_constraints_before: 5,
+                num_constraints_after: 2,
+                num_variables_before: 6,
+                num_variables_after: 4,
+                num_substituted: 1,
+                num_duplicates: 1,
+                num_trivial: 1,
+            }
+        );
+        assert_eq!(optimised.wire_mapping, vec![0, 1, 2, 3]);
+        let witness: Vec<Fr> = optimised.wire_mapping.iter().map(|w| witness[*w]).collect();
+        assert!(is_satisfied(&optimised.r1cs, &witness));
+        assert_eq!(to_json(&optimised.r1cs).constraints.len(), 2);
+    }
+}
@@ -0,0 +1,153 @@
+// the result of a cli command: the files it wrote and its results, printed as a single json object on stdout
+// with `--json`, and the exit codes of the failure classes.
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::path::Path;
+use std::time::Instant;
+use tiny_keccak::{Hasher, Keccak};
+
+pub const EXIT_OK: i32 = 0;
+// an unexpected error, e.g. a missing or malformed input file
+pub const EXIT_ERROR: i32 = 1;
+// invalid command line arguments
+pub const EXIT_USAGE: i32 = 2;
+// verify, recursive-verify and verify-evm: the proof is rejected
+pub const EXIT_INVALID_PROOF: i32 = 3;
+// check-aggregation: the aggregated proof doesn't match the proofs
+pub const EXIT_CHECK_FAILED: i32 = 4;
+// recursive-prove: some proofs of the list cannot be aggregated
+pub const EXIT_AGGREGATION_REJECTED: i32 = 5;
+// queue run: some jobs failed
+pub const EXIT_JOBS_FAILED: i32 = 6;
+// analyse: the circuit needs a larger setup than the largest supported one
+pub const EXIT_SETUP_TOO_LARGE: i32 = 7;
+
+struct Report {
+    command: String,
+    json: bool,
+    started: Instant,
+    outputs: Vec<String>,
+    results: Map<String, Value>,
+}
+
+thread_local! {
+    static REPORT: RefCell<Option<Report>> = RefCell::default();
+}
+
+// start recording the result of `command`
+pub fn init(command: &str, json: bool) {
+    REPORT.with(|r| {
+        *r.borrow_mut() = Some(Report {
+            command: command.to_owned(),
+            json,
+            started: Instant::now(),
+            outputs: vec![],
+            results: Map::new(),
+        })
+    });
+}
+
+// print the result as json, as known once the arguments are parsed
+pub fn set_json(json: bool) {
+    REPORT.with(|r| {
+        if let Some(r) = r.borrow_mut().as_mut() {
+            r.json = json;
+        }
+    });
+}
+
+pub fn is_json() -> bool {
+    REPORT.with(|r| r.borrow().as_ref().map(|r| r.json)) == Some(true)
+}
+
+// record a file written by the command
+pub fn written<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref().display().to_string();
+    REPORT.with(|r| {
+        if let Some(r) = r.borrow_mut().as_mut() {
+            r.outputs.push(path);
+        }
+    });
+}
+
+// record a result of the command
+pub fn set<T: Serialize + ?Sized>(key: &str, value: &T) {
+    let value = serde_json::to_value(value).unwrap_or(Value::Null);
+    REPORT.with(|r| {
+        if let Some(r) = r.borrow_mut().as_mut() {
+            r.results.insert(key.to_owned(), value);
+        }
+    });
+}
+
+fn file_hash(path: &str) -> Option<String> {
+    let content = std::fs::read(path).ok()?;
+    let mut hasher = Keccak::v256();
+    hasher.update(&content);
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    Some(format!("0x{}", hash.iter().map(|b| format!("{:02x}", b)).collect::<String>()))
+}
+
+fn print(exit_code: i32, error: Option<&str>) {
+    REPORT.with(|r| {
+        let r = r.borrow();
+        let r = match r.as_ref() {
+            Some(r) if r.json => r,
+            _ => return,
+        };
+        let outputs: Vec<Value> = r
+            .outputs
+            .iter()
+            .map(|path| json!({ "path": path, "keccak256": file_hash(path) }))
+            .collect();
+        let mut result = json!({
+            "command": r.command,
+            "ok": exit_code == EXIT_OK,
+            "exit_code": exit_code,
+            "elapsed_ms": r.started.elapsed().as_millis() as u64,
+            "outputs": outputs,
+            "results": r.results,
+        });
+        if let Some(error) = error {
+            result["error"] = Value::String(error.to_owned());
+        }
+        println!("{}", result);
+    });
+}
+
+// print the result of a successful command
+pub fn finish() {
+    print(EXIT_OK, None);
+}
+
+// print the result of a failed command, and exit with the code of its failure class
+pub fn fail(exit_code: i32, error: &str) -> ! {
+    log::error!("{}", error);
+    print(exit_code, Some(error));
+    std::process::exit(exit_code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        init("verify", false);
+        assert!(!is_json());
+        set_json(true);
+        assert!(is_json());
+        set("valid", &true);
+        written("Cargo.toml");
+        REPORT.with(|r| {
+            let r = r.borrow();
+            let r = r.as_ref().unwrap();
+            assert_eq!(r.results["valid"], Value::Bool(true));
+            assert_eq!(r.outputs, vec!["Cargo.toml".to_owned()]);
+        });
+        assert!(file_hash("Cargo.toml").unwrap().starts_with("0x"));
+        assert_eq!(file_hash("no_such_file"), None);
+    }
+}
@@ -1,25 +1,35 @@
 // Most of this file is forked from source codes of [Matter Labs's zkSync](https://github.com/matter-labs/zksync)
 use crate::bellman_ce::bn256::Bn256;
 use crate::bellman_ce::{
-    kate_commitment::{Crs, CrsForLagrangeForm, CrsForMonomialForm},
-    pairing::Engine,
+    kate_commitment::{commit_using_monomials, Crs, CrsForLagrangeForm, CrsForMonomialForm},
+    pairing::{
+        bn256::Fr,
+        ff::{Field, PrimeField},
+        Engine,
+    },
     plonk::{
         better_cs::adaptor::TranspilationVariant,
-        better_cs::cs::PlonkCsWidth4WithNextStepParams,
+        better_cs::cs::{Circuit as PlonkCircuit, PlonkCsWidth4WithNextStepParams},
+        better_cs::generator::GeneratorAssembly,
         better_cs::keys::{Proof, SetupPolynomials, VerificationKey},
         commitments::transcript::keccak_transcript::RollingKeccakTranscript,
-        is_satisfied_using_one_shot_check, make_verification_key, prove, prove_by_steps, setup,
+        is_satisfied_using_one_shot_check, make_verification_key,
+        polynomials::Polynomial,
+        prove, prove_by_steps, prove_native_by_steps, setup,
     },
     worker::Worker,
     Circuit, ScalarEngine, SynthesisError,
 };
 use crate::circom_circuit::CircomCircuit;
-use crate::transpile::{transpile_with_gates_count, ConstraintStat, TranspilerWrapper};
+use crate::gate_circuit::GateCircuit;
+use crate::transpile::{transpile_with_gates_count, variant_name, ComponentStat, ConstraintStat, TranspilerWrapper};
 
 type E = Bn256;
 use franklin_crypto::plonk::circuit::bigint::field::RnsParameters;
 use franklin_crypto::rescue::rescue_transcript::RescueTranscriptForRNS;
 use franklin_crypto::rescue::RescueEngine;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 pub const AUX_OFFSET: usize = 1;
 
@@ -50,8 +60,9 @@ pub fn gen_key_monomial_form(power: u32) -> Result<Crs<E, CrsForMonomialForm>, a
 pub struct SetupForProver {
     setup_polynomials: SetupPolynomials<E, PlonkCsWidth4WithNextStepParams>,
     hints: Vec<(usize, TranspilationVariant)>,
-    key_monomial_form: Crs<E, CrsForMonomialForm>,
-    key_lagrange_form: Option<Crs<E, CrsForLagrangeForm>>,
+    // shared by the setups of all the circuits a server keeps in memory
+    key_monomial_form: Arc<Crs<E, CrsForMonomialForm>>,
+    key_lagrange_form: Option<Arc<Crs<E, CrsForLagrangeForm>>>,
 }
 
 // circuit analysis result
@@ -66,42 +77,275 @@ pub struct AnalyseResult {
     pub num_hints: usize,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub constraint_stats: Vec<ConstraintStat>,
+    // constraints and gates by constraint shape and by transpilation variant, the most gates first
+    pub shape_histogram: Vec<HistogramEntry>,
+    pub variant_histogram: Vec<HistogramEntry>,
+    pub most_expensive_constraints: Vec<ExpensiveConstraint>,
+    // gates by circuit component, if the circuit has its constraint components
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<ComponentStat>,
+    pub resources: ResourceEstimate,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HistogramEntry {
+    pub key: String,
+    pub num_constraints: usize,
+    pub num_gates: usize,
+}
+
+// a constraint, with its shape and how it was transpiled
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExpensiveConstraint {
+    pub name: String,
+    pub num_gates: usize,
+    pub shape: String,
+    pub variant: String,
+}
+
+const NUM_MOST_EXPENSIVE_CONSTRAINTS: usize = 10;
+
+// count the constraints and gates of each key, the most gates first and then by key
+fn histogram<I: Iterator<Item = (String, usize)>>(items: I) -> Vec<HistogramEntry> {
+    let mut counts: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    for (key, num_gates) in items {
+        let count = counts.entry(key).or_default();
+        count.0 += 1;
+        count.1 += num_gates;
+    }
+    let mut entries: Vec<HistogramEntry> = counts
+        .into_iter()
+        .map(|(key, (num_constraints, num_gates))| HistogramEntry {
+            key,
+            num_constraints,
+            num_gates,
+        })
+        .collect();
+    entries.sort_by(|x, y| y.num_gates.cmp(&x.num_gates));
+    entries
+}
+
+// the setup needed to prove a circuit, and rough estimates of the memory and time it takes; the times are
+// extrapolated from a small multiexp, FFT and lagrange_form SRS computation timed on a machine, if any
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ResourceEstimate {
+    // power of two of the evaluation domain, the lagrange_form SRS must be of this size
+    pub domain_power: u32,
+    // power of two of the smallest monomial_form SRS that fits the circuit
+    pub srs_power: u32,
+    pub srs_monomial_form: String,
+    pub prove_memory_bytes: u64,
+    pub prove_secs: Option<f64>,
+    pub dump_lagrange_memory_bytes: u64,
+    pub dump_lagrange_secs: Option<f64>,
+}
+
+// in-memory sizes of a field element, an affine and a projective G1 point
+const FR_BYTES: u64 = 32;
+const G1_AFFINE_BYTES: u64 = 72;
+const G1_PROJECTIVE_BYTES: u64 = 96;
+// the setup polynomials: 7 selectors (q_a..q_d, q_m, q_const, q_d_next) and 4 permutations
+const NUM_SETUP_POLYNOMIALS: u64 = 11;
+// while computing the quotient, the width-4 prover keeps over the 4x coset domain the setup polynomials,
+// the 4 wires and the next step of d, the grand product and its shift, the inputs and the quotient itself:
+// counted from the prover, not measured
+const PROVE_NUM_LDES: u64 = NUM_SETUP_POLYNOMIALS + 5 + 2 + 2;
+// it commits to 4 wires, the grand product, 4 quotient chunks and 2 openings
+const PROVE_NUM_COMMITMENTS: f64 = 11.0;
+// a coset FFT for each LDE, and the inverse FFTs of the quotient and of the 4 wires,
+// all counted over the 4x domain (an overestimate for the wires)
+const PROVE_NUM_FFTS: f64 = (PROVE_NUM_LDES + 1 + 4) as f64;
+
+const CALIBRATION_POW2: u32 = 12;
+
+// seconds taken by the operations of size 2^CALIBRATION_POW2
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    pub multiexp_secs: f64,
+    pub fft_secs: f64,
+    pub lagrange_secs: f64,
+}
+
+impl Calibration {
+    pub fn measure() -> Result<Self, anyhow::Error> {
+        use std::time::Instant;
+        let worker = Worker::new();
+        let size = 1 << CALIBRATION_POW2;
+        let key_monomial_form = Crs::<E, CrsForMonomialForm>::crs_42(size, &worker);
+        // pseudo random coefficients, a multiexp of small scalars would be unrealistically fast
+        let generator = Fr::from_str("7").unwrap();
+        let mut coeffs = Vec::with_capacity(size);
+        let mut coeff = generator;
+        for _ in 0..size {
+            coeffs.push(coeff);
+            coeff.mul_assign(&generator);
+        }
+        let poly = Polynomial::from_coeffs(coeffs)?;
+
+        let t = Instant::now();
+        commit_using_monomials(&poly, &key_monomial_form, &worker)?;
+        let multiexp_secs = t.elapsed().as_secs_f64();
+        let t = Instant::now();
+        poly.fft(&worker);
+        let fft_secs = t.elapsed().as_secs_f64();
+        let t = Instant::now();
+        Crs::<E, CrsForLagrangeForm>::from_powers(&key_monomial_form, size, &worker);
+        let lagrange_secs = t.elapsed().as_secs_f64();
+        Ok(Calibration {
+            multiexp_secs,
+            fft_secs,
+            lagrange_secs,
+        })
+    }
+}
+
+// n log n, relative to the calibration size
+fn n_log_n_ratio(power: u32) -> f64 {
+    (1u64 << power) as f64 * power as f64 / ((1u64 << CALIBRATION_POW2) as f64 * CALIBRATION_POW2 as f64)
 }
 
-// analyse a circuit
-pub fn analyse<E: Engine>(circuit: CircomCircuit<E>) -> Result<AnalyseResult, anyhow::Error> {
+// estimate the resources to prove a circuit of `num_gates` gates, the times only with a calibration
+pub fn estimate_resources(num_gates: usize, calibration: Option<&Calibration>) -> ResourceEstimate {
+    // the setup pads the gates to a power of two minus one
+    let domain_power = (num_gates + 1).next_power_of_two().trailing_zeros();
+    let srs_power = std::cmp::max(domain_power, SETUP_MIN_POW2);
+    let n = 1u64 << domain_power;
+    let srs_size = 1u64 << srs_power;
+    let scale = n as f64 / (1u64 << CALIBRATION_POW2) as f64;
+    ResourceEstimate {
+        domain_power,
+        srs_power,
+        srs_monomial_form: format!("setup_2^{}.key", srs_power),
+        prove_memory_bytes: srs_size * G1_AFFINE_BYTES + (NUM_SETUP_POLYNOMIALS + PROVE_NUM_LDES * 4) * n * FR_BYTES,
+        prove_secs: calibration
+            .map(|c| PROVE_NUM_COMMITMENTS * c.multiexp_secs * scale + PROVE_NUM_FFTS * c.fft_secs * n_log_n_ratio(domain_power + 2)),
+        dump_lagrange_memory_bytes: srs_size * G1_AFFINE_BYTES + n * (G1_AFFINE_BYTES + G1_PROJECTIVE_BYTES),
+        dump_lagrange_secs: calibration.map(|c| c.lagrange_secs * n_log_n_ratio(domain_power)),
+    }
+}
+
+// analyse a circuit, estimating the proving times with the calibration of a machine if given
+pub fn analyse<E: Engine>(circuit: CircomCircuit<E>, calibration: Option<&Calibration>) -> Result<AnalyseResult, anyhow::Error> {
     let mut transpiler = TranspilerWrapper::<E, PlonkCsWidth4WithNextStepParams>::new();
-    let mut result = AnalyseResult {
-        num_inputs: circuit.r1cs.num_inputs,
-        num_aux: circuit.r1cs.num_aux,
-        num_variables: circuit.r1cs.num_variables,
-        num_constraints: circuit.r1cs.constraints.len(),
-        num_nontrivial_constraints: 0,
-        num_gates: 0,
-        num_hints: 0,
-        constraint_stats: Vec::new(),
-    };
+    let (num_inputs, num_aux, num_variables, num_constraints) = (
+        circuit.r1cs.num_inputs,
+        circuit.r1cs.num_aux,
+        circuit.r1cs.num_variables,
+        circuit.r1cs.constraints.len(),
+    );
     circuit
         .synthesize(&mut transpiler)
         .expect("sythesize into traspilation must succeed");
-    result.num_nontrivial_constraints = transpiler.constraint_stats.len();
-    result.num_gates = transpiler.num_gates();
-    result.constraint_stats = transpiler.constraint_stats.clone();
+    let num_gates = transpiler.num_gates();
+    let constraint_stats = transpiler.constraint_stats.clone();
+    let components = transpiler.components().to_vec();
+    let shapes: Vec<String> = transpiler.constraint_shapes.iter().map(|s| s.key()).collect();
     let hints = transpiler.into_hints();
-    result.num_hints = hints.len();
-    Ok(result)
+    // a hint for each constraint, by the order they were enforced in
+    let mut variants = vec!["unknown".to_owned(); constraint_stats.len()];
+    for (index, variant) in &hints {
+        if let Some(name) = variants.get_mut(*index) {
+            *name = variant_name(variant);
+        }
+    }
+
+    let gates = constraint_stats.iter().map(|c| c.num_gates);
+    let shape_histogram = histogram(shapes.iter().cloned().zip(gates.clone()));
+    let variant_histogram = histogram(variants.iter().cloned().zip(gates));
+    let mut most_expensive_constraints: Vec<ExpensiveConstraint> = constraint_stats
+        .iter()
+        .zip(shapes.into_iter().zip(variants))
+        .map(|(stat, (shape, variant))| ExpensiveConstraint {
+            name: stat.name.clone(),
+            num_gates: stat.num_gates,
+            shape,
+            variant,
+        })
+        .collect();
+    most_expensive_constraints.sort_by(|x, y| y.num_gates.cmp(&x.num_gates));
+    most_expensive_constraints.truncate(NUM_MOST_EXPENSIVE_CONSTRAINTS);
+
+    Ok(AnalyseResult {
+        num_inputs,
+        num_aux,
+        num_variables,
+        num_constraints,
+        num_nontrivial_constraints: constraint_stats.len(),
+        num_gates,
+        num_hints: hints.len(),
+        constraint_stats,
+        shape_histogram,
+        variant_histogram,
+        most_expensive_constraints,
+        components,
+        resources: estimate_resources(num_gates, calibration),
+    })
+}
+
+impl AnalyseResult {
+    // fail before anyone fetches a setup that is too large, or one that doesn't exist
+    pub fn check_setup_range(&self) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(
+            self.resources.srs_power <= SETUP_MAX_POW2,
+            "the circuit has {} gates, it needs a 2^{} setup, larger than the largest supported 2^{} setup",
+            self.num_gates,
+            self.resources.srs_power,
+            SETUP_MAX_POW2
+        );
+        Ok(())
+    }
 }
 
 impl SetupForProver {
     // meta-data preparation before proving a circuit
     pub fn prepare_setup_for_prover<C: Circuit<E> + Clone>(
         circuit: C,
-        key_monomial_form: Crs<E, CrsForMonomialForm>,
+        key_monomial_form: impl Into<Arc<Crs<E, CrsForMonomialForm>>>,
         key_lagrange_form: Option<Crs<E, CrsForLagrangeForm>>,
     ) -> Result<Self, anyhow::Error> {
         let (gates_count, hints) = transpile_with_gates_count(circuit.clone())?;
         log::info!("transpile done, gates_count {} hints size {}", gates_count, hints.len());
+        Self::prepare_setup_for_prover_with_hints(circuit, hints, key_monomial_form, key_lagrange_form)
+    }
+
+    // meta-data preparation of a circuit already transpiled into hints
+    pub fn prepare_setup_for_prover_with_hints<C: Circuit<E> + Clone>(
+        circuit: C,
+        hints: Vec<(usize, TranspilationVariant)>,
+        key_monomial_form: impl Into<Arc<Crs<E, CrsForMonomialForm>>>,
+        key_lagrange_form: Option<Crs<E, CrsForLagrangeForm>>,
+    ) -> Result<Self, anyhow::Error> {
         let setup_polynomials = setup(circuit, &hints)?;
+        Self::check_setup_size(&setup_polynomials)?;
+
+        Ok(SetupForProver {
+            setup_polynomials,
+            hints,
+            key_monomial_form: key_monomial_form.into(),
+            key_lagrange_form: key_lagrange_form.map(Arc::new),
+        })
+    }
+
+    // meta-data preparation of a native PLONK circuit, which needs no transpilation
+    pub fn prepare_setup_for_native_prover(
+        circuit: &GateCircuit<E>,
+        key_monomial_form: impl Into<Arc<Crs<E, CrsForMonomialForm>>>,
+    ) -> Result<Self, anyhow::Error> {
+        let mut assembly = GeneratorAssembly::<E, PlonkCsWidth4WithNextStepParams>::new();
+        circuit.synthesize(&mut assembly)?;
+        assembly.finalize();
+        let setup_polynomials = assembly.setup(&Worker::new())?;
+        Self::check_setup_size(&setup_polynomials)?;
+
+        Ok(SetupForProver {
+            setup_polynomials,
+            hints: vec![],
+            key_monomial_form: key_monomial_form.into(),
+            key_lagrange_form: None,
+        })
+    }
+
+    fn check_setup_size(setup_polynomials: &SetupPolynomials<E, PlonkCsWidth4WithNextStepParams>) -> Result<(), anyhow::Error> {
         let size = setup_polynomials.n.next_power_of_two().trailing_zeros();
         log::info!("circuit setup_polynomials.n {:?} size {}", setup_polynomials.n, size);
         let setup_power_of_two = std::cmp::max(size, SETUP_MIN_POW2);
@@ -109,18 +353,22 @@ impl SetupForProver {
             (SETUP_MIN_POW2..=SETUP_MAX_POW2).contains(&setup_power_of_two),
             "setup power of two is not in the correct range"
         );
+        Ok(())
+    }
 
-        Ok(SetupForProver {
-            setup_polynomials,
-            hints,
-            key_monomial_form,
-            key_lagrange_form,
-        })
+    // prove with a lagrange_form SRS, or with the monomial_form one only
+    pub fn set_key_lagrange_form(&mut self, key_lagrange_form: Option<Arc<Crs<E, CrsForLagrangeForm>>>) {
+        self.key_lagrange_form = key_lagrange_form;
+    }
+
+    // the domain size of the circuit
+    pub fn domain_size(&self) -> usize {
+        self.setup_polynomials.n.next_power_of_two()
     }
 
     // generate a verification key for a circuit
     pub fn make_verification_key(&self) -> Result<VerificationKey<E, PlonkCsWidth4WithNextStepParams>, SynthesisError> {
-        make_verification_key(&self.setup_polynomials, &self.key_monomial_form)
+        make_verification_key(&self.setup_polynomials, &*self.key_monomial_form)
     }
 
     // quickly valiate whether a witness is satisfied
@@ -135,13 +383,13 @@ impl SetupForProver {
         transcript: &str,
     ) -> Result<Proof<E, PlonkCsWidth4WithNextStepParams>, SynthesisError> {
         is_satisfied_using_one_shot_check(circuit.clone(), &self.hints).expect("must satisfy");
-        match &self.key_lagrange_form {
+        match self.key_lagrange_form.as_deref() {
             Some(key_lagrange_form) => match transcript {
                 "keccak" => prove::<_, _, RollingKeccakTranscript<<E as ScalarEngine>::Fr>>(
                     circuit,
                     &self.hints,
                     &self.setup_polynomials,
-                    &self.key_monomial_form,
+                    &*self.key_monomial_form,
                     key_lagrange_form,
                 ),
                 _ => {
@@ -154,7 +402,7 @@ impl SetupForProver {
                     &self.hints,
                     &self.setup_polynomials,
                     None,
-                    &self.key_monomial_form,
+                    &*self.key_monomial_form,
                     None,
                 ),
                 "rescue" => {
@@ -164,7 +412,7 @@ impl SetupForProver {
                         &self.hints,
                         &self.setup_polynomials,
                         None,
-                        &self.key_monomial_form,
+                        &*self.key_monomial_form,
                         Some((&bn256_param, &rns_param)),
                     )
                 }
@@ -175,10 +423,45 @@ impl SetupForProver {
         }
     }
 
+    // generate a plonk proof for a native PLONK circuit, with witness loaded, using the monomial_form SRS only
+    pub fn prove_native(
+        &self,
+        circuit: &GateCircuit<E>,
+        transcript: &str,
+    ) -> Result<Proof<E, PlonkCsWidth4WithNextStepParams>, anyhow::Error> {
+        circuit.check_witness()?;
+        let proof = match transcript {
+            "keccak" => prove_native_by_steps::<_, _, RollingKeccakTranscript<<E as ScalarEngine>::Fr>>(
+                circuit,
+                &self.setup_polynomials,
+                None,
+                &*self.key_monomial_form,
+                None,
+            )?,
+            "rescue" => {
+                let (bn256_param, rns_param) = get_default_rescue_transcript_params();
+                prove_native_by_steps::<_, _, RescueTranscriptForRNS<E>>(
+                    circuit,
+                    &self.setup_polynomials,
+                    None,
+                    &*self.key_monomial_form,
+                    Some((&bn256_param, &rns_param)),
+                )?
+            }
+            _ => anyhow::bail!("invalid transcript. use 'keccak' or 'rescue'"),
+        };
+        Ok(proof)
+    }
+
+    // give back the monomial_form SRS, to reuse it for another circuit
+    pub fn into_key_monomial_form(self) -> Arc<Crs<E, CrsForMonomialForm>> {
+        self.key_monomial_form
+    }
+
     // calculate the lagrange_form SRS from a monomial_form SRS
     pub fn get_srs_lagrange_form_from_monomial_form(&self) -> Crs<E, CrsForLagrangeForm> {
         Crs::<E, CrsForLagrangeForm>::from_powers(
-            &self.key_monomial_form,
+            &*self.key_monomial_form,
             self.setup_polynomials.n.next_power_of_two(),
             &Worker::new(),
         )
@@ -225,4 +508,13 @@ mod tests {
     fn test_gen_key_monomial_form() {
         gen_key_monomial_form(10).unwrap();
     }
+
+    #[test]
+    fn test_histogram() {
+        let items = vec![("b", 1), ("a", 2), ("c", 3), ("b", 1)];
+        let entries = histogram(items.into_iter().map(|(key, num_gates)| (key.to_owned(), num_gates)));
+        let counts: Vec<_> = entries.iter().map(|e| (e.key.as_str(), e.num_constraints, e.num_gates)).collect();
+        // ties in gates are ordered by key
+        assert_eq!(counts, vec![("c", 1, 3), ("a", 1, 2), ("b", 2, 2)]);
+    }
 }
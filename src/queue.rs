@@ -0,0 +1,404 @@
+// a file-backed queue of proving jobs, one json file per job in the queue directory.
+// Jobs survive a crash of the runner: a running job records the pid of its runner and of the process running it, and a
+// job still marked running by a dead runner was interrupted, and is retried from scratch once that process is killed.
+// A lock file holding the runner pid keeps a second runner out.
+use anyhow::format_err;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the subcommands that can be queued
+pub const QUEUED_SUBCOMMANDS: [&str; 2] = ["prove", "recursive-prove"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    // the plonkit subcommand and its arguments, e.g. ["prove", "-m", "setup_2^20.key"]
+    pub args: Vec<String>,
+    // the working directory the arguments are relative to
+    pub dir: PathBuf,
+    // files written by the job
+    pub outputs: Vec<String>,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    // pid of the runner of a running job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<u32>,
+    // pid of the process the runner started for a running job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child: Option<u32>,
+    pub attempts: u32,
+    pub submitted_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    pub done: Vec<u64>,
+    pub failed: Vec<u64>,
+    pub recovered: Vec<u64>,
+}
+
+pub struct Queue {
+    dir: PathBuf,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+// whether a process is alive, from /proc or else with `kill -0` (unix only)
+fn is_alive(pid: u32) -> bool {
+    if Path::new("/proc/self").exists() {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .map_or(false, |s| s.success())
+}
+
+// kill a process with `kill` (unix only)
+fn kill(pid: u32) -> Result<(), anyhow::Error> {
+    let status = Command::new("kill")
+        .args(["-KILL", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()?;
+    anyhow::ensure!(status.success() || !is_alive(pid), "fail to kill process {}", pid);
+    Ok(())
+}
+
+// the lock of the runner of a queue, released when dropped
+pub struct RunnerLock {
+    path: PathBuf,
+}
+
+impl Drop for RunnerLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Queue {
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, anyhow::Error> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Queue {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    // take the runner lock of the queue, unless its holder is still alive. The lock is written aside then linked into
+    // place, so that it never exists without its pid
+    pub fn lock(&self) -> Result<RunnerLock, anyhow::Error> {
+        let path = self.dir.join("runner.lock");
+        let tmp = self.dir.join(format!("runner.lock.{}.tmp", std::process::id()));
+        let _ = fs::remove_file(&tmp);
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)?
+            .write_all(std::process::id().to_string().as_bytes())?;
+        let locked = self.link_lock(&tmp, &path);
+        fs::remove_file(&tmp)?;
+        locked?;
+        Ok(RunnerLock { path })
+    }
+
+    // link the written lock to the lock path, taking a stale lock over: it is renamed away first, which only one of
+    // concurrent runners manages, instead of removed after a check another runner may have passed too
+    fn link_lock(&self, tmp: &Path, path: &Path) -> Result<(), anyhow::Error> {
+        let stale = self.dir.join(format!("runner.lock.{}.stale", std::process::id()));
+        loop {
+            match fs::hard_link(tmp, path) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+            let holder = match fs::read_to_string(path) {
+                Ok(holder) => holder,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if let Ok(pid) = holder.trim().parse::<u32>() {
+                anyhow::ensure!(!is_alive(pid), "the queue is run by process {} already", pid);
+            }
+            match fs::rename(path, &stale) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+            // another runner took the stale lock over between the read and the rename, give its lock back
+            if fs::read_to_string(&stale)? != holder {
+                let _ = fs::hard_link(&stale, path);
+                fs::remove_file(&stale)?;
+                continue;
+            }
+            log::warn!("removed the stale runner lock {}", path.display());
+            fs::remove_file(&stale)?;
+        }
+    }
+
+    fn job_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{:08}.json", id))
+    }
+
+    // write to a temporary file then rename, so that a crash never leaves a truncated job file
+    fn save(&self, job: &Job) -> Result<(), anyhow::Error> {
+        let tmp = self.dir.join(format!("{:08}.json.tmp", job.id));
+        fs::write(&tmp, serde_json::to_string_pretty(job)?)?;
+        fs::rename(&tmp, self.job_path(job.id))?;
+        Ok(())
+    }
+
+    pub fn submit(&self, args: Vec<String>, dir: PathBuf, outputs: Vec<String>) -> Result<Job, anyhow::Error> {
+        anyhow::ensure!(
+            matches!(args.first(), Some(c) if QUEUED_SUBCOMMANDS.contains(&c.as_str())),
+            "only {} can be queued",
+            QUEUED_SUBCOMMANDS.join(" and ")
+        );
+        let mut id = self.jobs()?.last().map_or(0, |j| j.id + 1);
+        // claim the id by creating its file, in case of concurrent submits
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(self.job_path(id)) {
+                Ok(mut file) => {
+                    file.write_all(b"{}")?;
+                    break;
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => id += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let job = Job {
+            id,
+            args,
+            dir,
+            outputs,
+            status: JobStatus::Pending,
+            owner: None,
+            child: None,
+            attempts: 0,
+            submitted_at: now(),
+            updated_at: now(),
+        };
+        self.save(&job)?;
+        Ok(job)
+    }
+
+    // all the jobs, in submission order
+    pub fn jobs(&self) -> Result<Vec<Job>, anyhow::Error> {
+        let mut jobs = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new("json")) {
+                continue;
+            }
+            let content = fs::read_to_string(&path)?;
+            // a file claimed by a submit that is not finished yet
+            if content == "{}" {
+                continue;
+            }
+            jobs.push(serde_json::from_str::<Job>(&content).map_err(|e| format_err!("invalid job file {}: {}", path.display(), e))?);
+        }
+        jobs.sort_by_key(|j| j.id);
+        Ok(jobs)
+    }
+
+    pub fn get(&self, id: u64) -> Result<Job, anyhow::Error> {
+        let content = fs::read_to_string(self.job_path(id)).map_err(|_| format_err!("job {} not found", id))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn update(&self, job: &mut Job, status: JobStatus) -> Result<(), anyhow::Error> {
+        job.owner = match status {
+            JobStatus::Running => Some(std::process::id()),
+            _ => None,
+        };
+        // the process of a new attempt is recorded once started
+        job.child = None;
+        job.status = status;
+        job.updated_at = now();
+        self.save(job)
+    }
+
+    // record the process started for a running job, to kill it if the runner dies meanwhile
+    pub fn record_child(&self, id: u64, pid: u32) -> Result<(), anyhow::Error> {
+        let mut job = self.get(id)?;
+        job.child = Some(pid);
+        self.save(&job)
+    }
+
+    // jobs left running by a dead runner are queued again, unless they used up their attempts. Their process may have
+    // outlived the runner, it is killed so that it doesn't write the outputs along with the next attempt
+    pub fn recover(&self, max_attempts: u32) -> Result<Vec<u64>, anyhow::Error> {
+        let mut recovered = vec![];
+        let interrupted = |j: &Job| j.status == JobStatus::Running && !j.owner.map_or(false, is_alive);
+        for mut job in self.jobs()?.into_iter().filter(interrupted) {
+            if let Some(pid) = job.child.filter(|pid| is_alive(*pid)) {
+                log::warn!("killing process {} left running job {}", pid, job.id);
+                kill(pid)?;
+            }
+            let status = if job.attempts < max_attempts {
+                JobStatus::Pending
+            } else {
+                JobStatus::Failed {
+                    error: format!("interrupted after {} attempts", job.attempts),
+                }
+            };
+            self.update(&mut job, status)?;
+            recovered.push(job.id);
+        }
+        Ok(recovered)
+    }
+
+    // run the pending jobs in submission order until none is left, retrying a failed job up to `max_attempts` times.
+    // The queue is locked meanwhile
+    pub fn run<F>(&self, max_attempts: u32, mut exec: F) -> Result<RunSummary, anyhow::Error>
+    where
+        F: FnMut(&Job) -> Result<(), anyhow::Error>,
+    {
+        let _lock = self.lock()?;
+        let mut summary = RunSummary {
+            recovered: self.recover(max_attempts)?,
+            ..Default::default()
+        };
+        while let Some(mut job) = self.jobs()?.into_iter().find(|j| j.status == JobStatus::Pending) {
+            job.attempts += 1;
+            self.update(&mut job, JobStatus::Running)?;
+            log::info!("running job {} (attempt {}): {}", job.id, job.attempts, job.args.join(" "));
+            match exec(&job) {
+                Ok(()) => {
+                    self.update(&mut job, JobStatus::Done)?;
+                    log::info!("job {} done", job.id);
+                    summary.done.push(job.id);
+                }
+                Err(e) if job.attempts < max_attempts => {
+                    log::error!("job {} failed, will retry: {}", job.id, e);
+                    self.update(&mut job, JobStatus::Pending)?;
+                }
+                Err(e) => {
+                    log::error!("job {} failed: {}", job.id, e);
+                    self.update(&mut job, JobStatus::Failed { error: e.to_string() })?;
+                    summary.failed.push(job.id);
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_queue(name: &str) -> Queue {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        Queue::open(dir).unwrap()
+    }
+
+    fn args(command: &str) -> Vec<String> {
+        vec![command.to_owned(), "-m".to_owned(), "setup_2^10.key".to_owned()]
+    }
+
+    #[test]
+    fn test_submit_and_run() {
+        let queue = test_queue("plonkit_test_queue_run");
+        assert!(queue.submit(args("verify"), PathBuf::from("."), vec![]).is_err());
+        let first = queue
+            .submit(args("prove"), PathBuf::from("."), vec!["proof.bin".to_owned()])
+            .unwrap();
+        let second = queue.submit(args("recursive-prove"), PathBuf::from("."), vec![]).unwrap();
+        assert_eq!((first.id, second.id), (0, 1));
+
+        // the first job fails once then succeeds, the second always fails
+        let mut calls = vec![];
+        let summary = queue
+            .run(2, |job| {
+                calls.push(job.id);
+                if (job.id == 0 && job.attempts == 1) || job.id == 1 {
+                    anyhow::bail!("boom");
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(calls, vec![0, 0, 1, 1]);
+        assert_eq!(summary.done, vec![0]);
+        assert_eq!(summary.failed, vec![1]);
+        assert_eq!(queue.get(0).unwrap().status, JobStatus::Done);
+        assert_eq!(queue.get(1).unwrap().status, JobStatus::Failed { error: "boom".to_owned() });
+    }
+
+    #[test]
+    fn test_recover() {
+        let queue = test_queue("plonkit_test_queue_recover");
+        let mut job = queue.submit(args("prove"), PathBuf::from("."), vec![]).unwrap();
+        // the job of a live runner is left alone
+        job.attempts = 1;
+        queue.update(&mut job, JobStatus::Running).unwrap();
+        assert_eq!(queue.get(0).unwrap().owner, Some(std::process::id()));
+        assert!(queue.recover(2).unwrap().is_empty());
+
+        // a runner crashed in the middle of the job
+        job.owner = Some(u32::MAX);
+        queue.save(&job).unwrap();
+        assert_eq!(queue.recover(2).unwrap(), vec![0]);
+        assert_eq!(queue.get(0).unwrap().status, JobStatus::Pending);
+        assert_eq!(queue.get(0).unwrap().owner, None);
+
+        job.attempts = 2;
+        queue.save(&job).unwrap();
+        queue.recover(2).unwrap();
+        assert!(matches!(queue.get(0).unwrap().status, JobStatus::Failed { .. }));
+    }
+
+    #[test]
+    fn test_recover_kills_child() {
+        let queue = test_queue("plonkit_test_queue_recover_child");
+        let mut job = queue.submit(args("prove"), PathBuf::from("."), vec![]).unwrap();
+        job.attempts = 1;
+        queue.update(&mut job, JobStatus::Running).unwrap();
+        let mut child = Command::new("sleep").arg("60").spawn().unwrap();
+        queue.record_child(job.id, child.id()).unwrap();
+        assert_eq!(queue.get(0).unwrap().child, Some(child.id()));
+
+        // the runner crashed, its process of the job is still running
+        let mut job = queue.get(0).unwrap();
+        job.owner = Some(u32::MAX);
+        queue.save(&job).unwrap();
+        assert_eq!(queue.recover(2).unwrap(), vec![0]);
+        assert!(!child.wait().unwrap().success());
+        assert_eq!(queue.get(0).unwrap().child, None);
+    }
+
+    #[test]
+    fn test_runner_lock() {
+        let queue = test_queue("plonkit_test_queue_lock");
+        let lock = queue.lock().unwrap();
+        assert!(queue.lock().is_err());
+        assert!(queue.run(1, |_| Ok(())).is_err());
+        drop(lock);
+
+        // the lock of a dead runner is taken over
+        fs::write(queue.dir.join("runner.lock"), u32::MAX.to_string()).unwrap();
+        let _lock = queue.lock().unwrap();
+        assert_eq!(
+            fs::read_to_string(queue.dir.join("runner.lock")).unwrap(),
+            std::process::id().to_string()
+        );
+        // the written and the stale locks are cleaned up
+        assert_eq!(fs::read_dir(&queue.dir).unwrap().count(), 1);
+    }
+}
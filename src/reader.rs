@@ -17,7 +17,8 @@ use crate::bellman_ce::{
 };
 
 use crate::circom_circuit::{CircuitJson, R1CS};
-use crate::recursive::{AggregatedProof, RecursiveVerificationKey};
+use crate::gate_circuit::{GateCircuit, GateCircuitJson};
+use crate::recursive::{PaddedAggregatedProof, RecursiveProvingKey, RecursiveVerificationKey, RecursiveVkMetadata};
 
 /// load proof by filename
 pub fn load_proof<E: Engine>(filename: &str) -> Proof<E, PlonkCsWidth4WithNextStepParams> {
@@ -26,15 +27,24 @@ pub fn load_proof<E: Engine>(filename: &str) -> Proof<E, PlonkCsWidth4WithNextSt
 
 /// load multiple proofs form a list
 pub fn load_proofs_from_list<E: Engine>(list: &str) -> Vec<Proof<E, PlonkCsWidth4WithNextStepParams>> {
+    load_proofs_and_vk_idxs_from_list::<E>(list).0
+}
+
+/// load multiple proofs form a list, each line is a proof file optionally followed by the index of its vk (0 by default)
+pub fn load_proofs_and_vk_idxs_from_list<E: Engine>(list: &str) -> (Vec<Proof<E, PlonkCsWidth4WithNextStepParams>>, Vec<usize>) {
     let file = File::open(list).expect("read proof list file err");
     let lines: Vec<String> = BufReader::new(file).lines().map(|l| l.expect("could not parse line")).collect();
-    let proofs: Vec<Proof<E, PlonkCsWidth4WithNextStepParams>> = lines
+    let (proofs, vk_idxs): (Vec<Proof<E, PlonkCsWidth4WithNextStepParams>>, Vec<usize>) = lines
         .iter()
+        .filter(|l| !l.trim().is_empty())
         .map(|l| {
-            log::info!("reading {:?}", l);
-            load_proof::<E>(l)
+            let mut fields = l.split_whitespace();
+            let filename = fields.next().unwrap();
+            let vk_idx = fields.next().map_or(0, |idx| idx.parse().expect("invalid vk index"));
+            log::info!("reading {:?}", filename);
+            (load_proof::<E>(filename), vk_idx)
         })
-        .collect();
+        .unzip();
 
     assert!(!proofs.is_empty(), "no proof file found!");
 
@@ -43,12 +53,19 @@ pub fn load_proofs_from_list<E: Engine>(list: &str) -> Vec<Proof<E, PlonkCsWidth
         assert_eq!(p.num_inputs, num_inputs, "proofs num_inputs mismatch!");
     }
 
-    proofs
+    (proofs, vk_idxs)
+}
+
+/// load recursive proof file by filename, with the padding of its batch if any
+pub fn load_aggregated_proof(filename: &str) -> PaddedAggregatedProof {
+    PaddedAggregatedProof::read(File::open(filename).expect("read aggregated proof file err")).expect("read aggregated proof err")
 }
 
-/// load recursive proof file by filename
-pub fn load_aggregated_proof(filename: &str) -> AggregatedProof {
-    AggregatedProof::read(File::open(filename).expect("read aggregated proof file err")).expect("read aggregated proof err")
+/// load recursive proof json file by filename, `n` is the domain size of the recursive vk
+pub fn load_aggregated_proof_from_json(filename: &str, n: usize) -> PaddedAggregatedProof {
+    let file = File::open(filename).expect("read aggregated proof json file err");
+    let value: serde_json::Value = serde_json::from_reader(BufReader::new(file)).expect("read aggregated proof json err");
+    crate::recursive::aggregated_proof_from_json(&value, n).expect("parse aggregated proof json err")
 }
 
 /// load verification key file by filename
@@ -57,10 +74,50 @@ pub fn load_verification_key<E: Engine>(filename: &str) -> VerificationKey<E, Pl
     VerificationKey::<E, PlonkCsWidth4WithNextStepParams>::read(&mut reader).expect("read vk err")
 }
 
-/// load recursive verification key file by filename
+/// load multiple verification keys from a list, one vk file per line
+pub fn load_verification_keys_from_list<E: Engine>(list: &str) -> Vec<VerificationKey<E, PlonkCsWidth4WithNextStepParams>> {
+    let file = File::open(list).expect("read vk list file err");
+    let lines: Vec<String> = BufReader::new(file).lines().map(|l| l.expect("could not parse line")).collect();
+    let vks: Vec<VerificationKey<E, PlonkCsWidth4WithNextStepParams>> = lines
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| {
+            log::info!("reading {:?}", l);
+            load_verification_key::<E>(l)
+        })
+        .collect();
+
+    assert!(!vks.is_empty(), "no vk file found!");
+    vks
+}
+
+/// load recursive verification key file by filename with autodetect encoding (bin or json).
 pub fn load_recursive_verification_key(filename: &str) -> RecursiveVerificationKey<'static> {
     let mut reader = BufReader::with_capacity(1 << 24, File::open(filename).expect("read recursive vk file err"));
-    RecursiveVerificationKey::read(&mut reader).expect("read recursive vk err")
+    if filename.ends_with("json") {
+        serde_json::from_reader(reader).expect("read recursive vk json err")
+    } else {
+        RecursiveVerificationKey::read(&mut reader).expect("read recursive vk err")
+    }
+}
+
+/// load recursive proving key file by filename
+pub fn load_recursive_proving_key(filename: &str) -> RecursiveProvingKey {
+    let reader = BufReader::with_capacity(1 << 24, File::open(filename).expect("read recursive pk file err"));
+    RecursiveProvingKey::read(reader).expect("read recursive pk err")
+}
+
+/// load the metadata saved next to a recursive verification key file, if it was exported with one
+pub fn maybe_load_recursive_vk_metadata(vk_filename: &str) -> Result<Option<RecursiveVkMetadata>, anyhow::Error> {
+    let path = RecursiveVkMetadata::path_for(vk_filename);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = File::open(&path).map_err(|e| format_err!("read recursive vk metadata file {} err: {}", path.display(), e))?;
+    let metadata = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| format_err!("invalid recursive vk metadata {}: {}", path.display(), e))?;
+    Ok(Some(metadata))
 }
 
 /// get universal setup file by filename
@@ -115,6 +172,19 @@ pub fn load_witness_from_bin_file<E: Engine>(filename: &str) -> Vec<E::Fr> {
     load_witness_from_bin_reader::<E, BufReader<File>>(BufReader::new(reader)).expect("read witness failed")
 }
 
+/// load witness from bytes with autodetect encoding (wtns bin or json)
+pub fn load_witness_from_bytes<E: Engine>(bytes: &[u8]) -> Result<Vec<E::Fr>, anyhow::Error> {
+    if bytes.starts_with(b"wtns") {
+        load_witness_from_bin_reader::<E, _>(bytes)
+    } else {
+        let witness: Vec<String> = serde_json::from_slice(bytes)?;
+        witness
+            .iter()
+            .map(|x| E::Fr::from_str(x).ok_or_else(|| format_err!("invalid witness value {}", x)))
+            .collect()
+    }
+}
+
 /// load witness from u8 array
 pub fn load_witness_from_array<E: Engine>(buffer: Vec<u8>) -> Result<Vec<E::Fr>, anyhow::Error> {
     load_witness_from_bin_reader::<E, _>(buffer.as_slice())
@@ -174,6 +244,46 @@ fn load_witness_from_bin_reader<E: Engine, R: Read>(mut reader: R) -> Result<Vec
     Ok(result)
 }
 
+/// load a native PLONK circuit from a gate list json file
+pub fn load_gate_circuit(filename: &str) -> GateCircuit<Bn256> {
+    let reader = OpenOptions::new().read(true).open(filename).expect("unable to open.");
+    let json: GateCircuitJson = serde_json::from_reader(BufReader::new(reader)).expect("unable to read.");
+    GateCircuit::from_json(json).expect("invalid gate circuit")
+}
+
+/// load the wire mapping of an optimised circuit, a json array of the witness wire of each circuit wire
+pub fn load_wire_mapping(filename: &str) -> Vec<usize> {
+    let reader = OpenOptions::new().read(true).open(filename).expect("unable to open.");
+    serde_json::from_reader(BufReader::new(reader)).expect("unable to read.")
+}
+
+/// load the signal name of each wire from a circom .sym file, lines of `label,wire,component,name`
+/// (`label,wire,name` for older circom), signals optimized out have a wire of -1
+pub fn load_symbols(filename: &str) -> Result<BTreeMap<usize, String>, anyhow::Error> {
+    let reader = BufReader::new(File::open(filename).map_err(|e| format_err!("unable to open {}: {}", filename, e))?);
+    let mut wire_names = BTreeMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() < 3 {
+            bail!("invalid symbol line: {}", line);
+        }
+        let wire: i64 = fields[1].parse().map_err(|_| format_err!("invalid symbol line: {}", line))?;
+        let name = fields[fields.len() - 1];
+        // of the names of a wire, keep the outermost one
+        if wire >= 0 {
+            let entry = wire_names.entry(wire as usize).or_insert_with(|| name.to_owned());
+            if name.matches('.').count() < entry.matches('.').count() {
+                *entry = name.to_owned();
+            }
+        }
+    }
+    Ok(wire_names)
+}
+
 /// load r1cs file by filename with autodetect encoding (bin or json)
 pub fn load_r1cs(filename: &str) -> R1CS<Bn256> {
     if filename.ends_with("json") {
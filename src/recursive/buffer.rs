@@ -0,0 +1,111 @@
+// aggregate proofs held in memory, e.g. received over RPC, without writing them to proof list files first
+use super::{failed_old_proofs, prove_unchecked, AggregatedProof, InnerProofError, RecursiveProvingKey};
+use crate::bellman_ce::kate_commitment::{Crs, CrsForMonomialForm};
+use crate::bellman_ce::pairing::bn256::{Bn256, Fr};
+use crate::bellman_ce::plonk::{
+    better_cs::cs::PlonkCsWidth4WithNextStepParams,
+    better_cs::keys::{Proof as OldProof, VerificationKey as OldVerificationKey},
+};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AggregationError {
+    // the proof blob at `index` cannot be deserialized
+    InvalidProof { index: usize, reason: String },
+    // the vk blob at `index` cannot be deserialized
+    InvalidVerificationKey { index: usize, reason: String },
+    // the proof at `index` refers to a vk out of range
+    InvalidVkIndex { index: usize, vk_idx: usize },
+    // the proofs that fail the verification before aggregation
+    InnerProofs(Vec<(usize, InnerProofError)>),
+    Aggregation(anyhow::Error),
+    Serialization(std::io::Error),
+}
+
+impl fmt::Display for AggregationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AggregationError::InvalidProof { index, reason } => write!(f, "invalid proof {}: {}", index, reason),
+            AggregationError::InvalidVerificationKey { index, reason } => write!(f, "invalid verification key {}: {}", index, reason),
+            AggregationError::InvalidVkIndex { index, vk_idx } => write!(f, "proof {}: vk index {} out of range", index, vk_idx),
+            AggregationError::InnerProofs(failures) => {
+                write!(f, "{} proofs cannot be aggregated", failures.len())?;
+                for (index, e) in failures {
+                    write!(f, "\nproof {}: {}", index, e)?;
+                }
+                Ok(())
+            }
+            AggregationError::Aggregation(e) => write!(f, "fail to aggregate proofs: {}", e),
+            AggregationError::Serialization(e) => write!(f, "aggregated proof serialization err: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for AggregationError {}
+
+// the serialized aggregated proof (as written by `recursive-prove`), and its single public input
+#[derive(Debug, Clone)]
+pub struct AggregationOutput {
+    pub proof: Vec<u8>,
+    pub public_input: Fr,
+}
+
+fn read_proofs(blobs: &[&[u8]]) -> Result<Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>, AggregationError> {
+    blobs
+        .iter()
+        .enumerate()
+        .map(|(index, blob)| {
+            OldProof::read(*blob).map_err(|e| AggregationError::InvalidProof {
+                index,
+                reason: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn read_vks(blobs: &[&[u8]]) -> Result<Vec<OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>>, AggregationError> {
+    blobs
+        .iter()
+        .enumerate()
+        .map(|(index, blob)| {
+            OldVerificationKey::read(*blob).map_err(|e| AggregationError::InvalidVerificationKey {
+                index,
+                reason: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+// aggregate serialized proofs (proof.bin) with serialized vks (vk.bin), `vk_idxs` being the index of the vk for each proof.
+// The crs and the optional proving key are usually loaded once by the caller and reused.
+pub fn aggregate_from_bytes(
+    big_crs: Crs<Bn256, CrsForMonomialForm>,
+    proofs: &[&[u8]],
+    vks: &[&[u8]],
+    vk_idxs: &[usize],
+    vk_tree_depth: usize,
+    pk: Option<RecursiveProvingKey>,
+) -> Result<AggregationOutput, AggregationError> {
+    let old_proofs = read_proofs(proofs)?;
+    let old_vks = read_vks(vks)?;
+    if let Some((index, &vk_idx)) = vk_idxs.iter().enumerate().find(|(_, &vk_idx)| vk_idx >= old_vks.len()) {
+        return Err(AggregationError::InvalidVkIndex { index, vk_idx });
+    }
+    let failures = failed_old_proofs(&old_proofs, &old_vks, vk_idxs, vk_tree_depth).map_err(AggregationError::Aggregation)?;
+    if !failures.is_empty() {
+        return Err(AggregationError::InnerProofs(failures));
+    }
+
+    // the old proofs are checked above, don't verify them twice
+    let aggregated_proof =
+        prove_unchecked(big_crs, old_proofs, old_vks, vk_idxs.to_vec(), vk_tree_depth, pk).map_err(AggregationError::Aggregation)?;
+    let public_input = aggregated_proof.proof.inputs[0];
+    let mut proof = vec![];
+    aggregated_proof.write(&mut proof).map_err(AggregationError::Serialization)?;
+    Ok(AggregationOutput { proof, public_input })
+}
+
+// read an aggregated proof serialized by `aggregate_from_bytes` or `recursive-prove`
+pub fn aggregated_proof_from_bytes(bytes: &[u8]) -> Result<AggregatedProof, AggregationError> {
+    AggregatedProof::read(bytes).map_err(AggregationError::Serialization)
+}
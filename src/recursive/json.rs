@@ -0,0 +1,273 @@
+// read `AggregatedProof` back from its json form, which is the argument array of `verifyAggregatedProof`:
+// [recursive_input, serialized_proof, vk_indexes, individual_vks_inputs, subproofs_limbs], followed by the padding
+// of the batch if it was padded
+use super::{AggregatedProof, PaddedAggregatedProof, RecursiveAggregationCircuitBn256};
+use crate::bellman_ce::pairing::bn256::{Bn256, Fq, Fr, G1Affine};
+use crate::bellman_ce::pairing::ff::PrimeField;
+use crate::bellman_ce::pairing::CurveAffine;
+use crate::bellman_ce::plonk::better_better_cs::proof::Proof;
+use crate::calldata::words_from_json;
+use crate::utils::BigUint;
+use anyhow::{bail, format_err};
+use num_traits::Zero;
+use serde_json::Value;
+
+const STATE_WIDTH: usize = 4;
+// the main gate opens d(X) at z*omega, and its selector at z
+const D_NEXT_DILATION: usize = 1;
+const MAIN_GATE_IDX: usize = 0;
+const SERIALIZED_PROOF_LENGTH: usize = 34;
+
+fn fe_from_word<F: PrimeField>(word: &BigUint) -> Result<F, anyhow::Error> {
+    F::from_str(&word.to_str_radix(10)).ok_or_else(|| format_err!("{} is not a field element", word))
+}
+
+// the point at infinity is serialized as (0, 0)
+fn point_from_words(x: &BigUint, y: &BigUint) -> Result<G1Affine, anyhow::Error> {
+    if x.is_zero() && y.is_zero() {
+        return Ok(G1Affine::zero());
+    }
+    G1Affine::from_xy_checked(fe_from_word::<Fq>(x)?, fe_from_word::<Fq>(y)?).map_err(|e| format_err!("invalid point: {:?}", e))
+}
+
+struct WordReader<'a> {
+    words: &'a [BigUint],
+    pos: usize,
+}
+
+impl<'a> WordReader<'a> {
+    fn next(&mut self) -> Result<&'a BigUint, anyhow::Error> {
+        let word = self
+            .words
+            .get(self.pos)
+            .ok_or_else(|| format_err!("serialized proof is too short"))?;
+        self.pos += 1;
+        Ok(word)
+    }
+
+    fn fr(&mut self) -> Result<Fr, anyhow::Error> {
+        fe_from_word(self.next()?)
+    }
+
+    fn frs(&mut self, count: usize) -> Result<Vec<Fr>, anyhow::Error> {
+        (0..count).map(|_| self.fr()).collect()
+    }
+
+    fn point(&mut self) -> Result<G1Affine, anyhow::Error> {
+        let x = self.next()?;
+        let y = self.next()?;
+        point_from_words(x, y)
+    }
+
+    fn points(&mut self, count: usize) -> Result<Vec<G1Affine>, anyhow::Error> {
+        (0..count).map(|_| self.point()).collect()
+    }
+}
+
+// the inverse of the proof serialization for the recursive verifier. The domain size is not serialized,
+// `n` should be taken from the recursive vk.
+fn proof_from_words(
+    n: usize,
+    inputs: &[BigUint],
+    words: &[BigUint],
+) -> Result<Proof<Bn256, RecursiveAggregationCircuitBn256<'static>>, anyhow::Error> {
+    if words.len() != SERIALIZED_PROOF_LENGTH {
+        bail!(
+            "unexpected serialized proof length, expect {}, got {}",
+            SERIALIZED_PROOF_LENGTH,
+            words.len()
+        );
+    }
+    let mut reader = WordReader { words, pos: 0 };
+    let mut proof = Proof::empty();
+    proof.n = n;
+    proof.inputs = inputs.iter().map(fe_from_word).collect::<Result<_, _>>()?;
+    proof.state_polys_commitments = reader.points(STATE_WIDTH)?;
+    proof.copy_permutation_grand_product_commitment = reader.point()?;
+    proof.quotient_poly_parts_commitments = reader.points(STATE_WIDTH)?;
+    proof.state_polys_openings_at_z = reader.frs(STATE_WIDTH)?;
+    proof.state_polys_openings_at_dilations = vec![(D_NEXT_DILATION, STATE_WIDTH - 1, reader.fr()?)];
+    proof.gate_selectors_openings_at_z = vec![(MAIN_GATE_IDX, reader.fr()?)];
+    proof.copy_permutation_polys_openings_at_z = reader.frs(STATE_WIDTH - 1)?;
+    proof.copy_permutation_grand_product_opening_at_z_omega = reader.fr()?;
+    proof.quotient_poly_opening_at_z = reader.fr()?;
+    proof.linearization_poly_opening_at_z = reader.fr()?;
+    proof.opening_proof_at_z = reader.point()?;
+    proof.opening_proof_at_z_omega = reader.point()?;
+    Ok(proof)
+}
+
+// parse an aggregated proof from the json written by `recursive-prove`, `n` is the domain size of the recursive vk
+pub fn aggregated_proof_from_json(value: &Value, n: usize) -> Result<PaddedAggregatedProof, anyhow::Error> {
+    let parts = value.as_array().ok_or_else(|| format_err!("unexpected aggregated proof layout"))?;
+    if parts.len() != 5 && parts.len() != 6 {
+        bail!(
+            "unexpected aggregated proof layout, expect 5 parts and the padding, got {}",
+            parts.len()
+        );
+    }
+    let padding = parts.get(5).map(|p| serde_json::from_value(p.clone())).transpose()?;
+    let parts: Vec<Vec<BigUint>> = parts[..5].iter().map(words_from_json).collect::<Result<_, _>>()?;
+    let (recursive_input, serialized_proof, vk_idxs, individual_vk_inputs, aggr_limbs) =
+        (&parts[0], &parts[1], &parts[2], &parts[3], &parts[4]);
+
+    if vk_idxs.is_empty() || individual_vk_inputs.len() % vk_idxs.len() != 0 {
+        bail!(
+            "{} individual inputs cannot be split among {} proofs",
+            individual_vk_inputs.len(),
+            vk_idxs.len()
+        );
+    }
+    let individual_vk_idxs = vk_idxs
+        .iter()
+        .map(|idx| {
+            idx.to_str_radix(10)
+                .parse::<usize>()
+                .map_err(|_| format_err!("invalid vk index {}", idx))
+        })
+        .collect::<Result<_, _>>()?;
+
+    let proof = AggregatedProof {
+        proof: proof_from_words(n, recursive_input, serialized_proof)?,
+        individual_vk_inputs: individual_vk_inputs.iter().map(fe_from_word).collect::<Result<_, _>>()?,
+        individual_num_inputs: individual_vk_inputs.len() / vk_idxs.len(),
+        individual_vk_idxs,
+        aggr_limbs: aggr_limbs.iter().map(fe_from_word).collect::<Result<_, _>>()?,
+    };
+    PaddedAggregatedProof::new(proof, padding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bellman_ce::pairing::bn256::G2Affine;
+    use crate::bellman_ce::pairing::CurveProjective;
+    use crate::recursive::{AggregationPadding, RecursiveVerificationKey};
+    use serde_json::json;
+
+    #[test]
+    fn test_point_from_words() {
+        assert!(point_from_words(&BigUint::zero(), &BigUint::zero()).unwrap().is_zero());
+        // the generator of bn256 G1 is (1, 2)
+        let g = point_from_words(&BigUint::from(1u64), &BigUint::from(2u64)).unwrap();
+        assert_eq!(g, G1Affine::one());
+        assert!(point_from_words(&BigUint::from(1u64), &BigUint::from(3u64)).is_err());
+    }
+
+    fn fr(i: u64) -> Fr {
+        Fr::from_str(&i.to_string()).unwrap()
+    }
+
+    // distinct points and field elements, so that a misplaced one is caught
+    fn point(i: u64) -> G1Affine {
+        G1Affine::one().mul(fr(i).into_repr()).into_affine()
+    }
+
+    fn aggregated_proof() -> AggregatedProof {
+        let mut proof = Proof::empty();
+        proof.n = 1 << 20;
+        proof.inputs = vec![fr(1)];
+        proof.state_polys_commitments = (2..6).map(point).collect();
+        proof.copy_permutation_grand_product_commitment = point(6);
+        proof.quotient_poly_parts_commitments = (7..11).map(point).collect();
+        proof.state_polys_openings_at_z = (11..15).map(fr).collect();
+        proof.state_polys_openings_at_dilations = vec![(D_NEXT_DILATION, STATE_WIDTH - 1, fr(15))];
+        proof.gate_selectors_openings_at_z = vec![(MAIN_GATE_IDX, fr(16))];
+        proof.copy_permutation_polys_openings_at_z = (17..20).map(fr).collect();
+        proof.copy_permutation_grand_product_opening_at_z_omega = fr(20);
+        proof.quotient_poly_opening_at_z = fr(21);
+        proof.linearization_poly_opening_at_z = fr(22);
+        proof.opening_proof_at_z = point(23);
+        proof.opening_proof_at_z_omega = point(24);
+        AggregatedProof {
+            proof,
+            individual_vk_inputs: (25..31).map(fr).collect(),
+            individual_num_inputs: 3,
+            individual_vk_idxs: vec![1, 0],
+            aggr_limbs: (31..47).map(fr).collect(),
+        }
+    }
+
+    #[test]
+    fn test_aggregated_proof_round_trip() {
+        let expected = aggregated_proof();
+        let mut json = serde_json::to_value(&expected).unwrap();
+        let actual = aggregated_proof_from_json(&json, expected.proof.n).unwrap();
+        assert!(actual.padding.is_none());
+        let (mut expected_bytes, mut actual_bytes) = (vec![], vec![]);
+        expected.write(&mut expected_bytes).unwrap();
+        actual.write(&mut actual_bytes).unwrap();
+        assert_eq!(actual_bytes, expected_bytes);
+
+        // 5 inputs cannot be split among 2 proofs
+        json[3].as_array_mut().unwrap().pop();
+        assert!(aggregated_proof_from_json(&json, expected.proof.n).is_err());
+        json[1].as_array_mut().unwrap().pop();
+        assert!(aggregated_proof_from_json(&json, expected.proof.n).is_err());
+    }
+
+    #[test]
+    fn test_padded_aggregated_proof_round_trip() {
+        let padding = AggregationPadding {
+            num_proofs: 2,
+            num_padding: 1,
+            padding_vk_idx: 0,
+            padding_proof: "00".to_owned(),
+        };
+        let expected = PaddedAggregatedProof::new(aggregated_proof(), Some(padding.clone())).unwrap();
+        assert_eq!(expected.num_real_proofs(), 1);
+        assert!(!expected.is_dummy(0) && expected.is_dummy(1));
+
+        // the padding travels with the json, and with the bytes after the bare proof
+        let json = expected.to_json().unwrap();
+        let actual = aggregated_proof_from_json(&json, expected.proof.n).unwrap();
+        assert_eq!(actual.padding.as_ref(), Some(&padding));
+        let mut bytes = vec![];
+        expected.write(&mut bytes).unwrap();
+        assert_eq!(PaddedAggregatedProof::read(&bytes[..]).unwrap().padding, Some(padding.clone()));
+        let mut bare_bytes = vec![];
+        expected.proof.write(&mut bare_bytes).unwrap();
+        assert!(bytes.starts_with(&bare_bytes));
+        assert!(PaddedAggregatedProof::read(&bytes[..bytes.len() - 1]).is_err());
+
+        // the padding must be of the aggregated proofs
+        let padding = AggregationPadding { num_proofs: 3, ..padding };
+        assert!(PaddedAggregatedProof::new(aggregated_proof(), Some(padding)).is_err());
+    }
+
+    #[test]
+    fn test_recursive_vk_round_trip() {
+        let points = |ids: std::ops::Range<u64>| ids.map(|i| serde_json::to_value(point(i)).unwrap()).collect::<Vec<_>>();
+        let g2 = serde_json::to_value(G2Affine::one()).unwrap();
+        let vk: RecursiveVerificationKey = serde_json::from_value(json!({
+            "n": (1 << 20) - 1,
+            "num_inputs": 1,
+            "state_width": 4,
+            "num_witness_polys": 0,
+            "gate_setup_commitments": points(1..8),
+            "gate_selectors_commitments": points(8..10),
+            "permutation_commitments": points(10..14),
+            "total_lookup_entries_length": 0,
+            "lookup_selector_commitment": null,
+            "lookup_tables_commitments": [],
+            "lookup_table_type_commitment": null,
+            "non_residues": [fr(5), fr(7), fr(10)],
+            "g2_elements": [g2, g2],
+        }))
+        .unwrap();
+
+        let json = serde_json::to_string_pretty(&vk).unwrap();
+        let actual: RecursiveVerificationKey = serde_json::from_str(&json).unwrap();
+        let (mut expected_bytes, mut actual_bytes) = (vec![], vec![]);
+        vk.write(&mut expected_bytes).unwrap();
+        actual.write(&mut actual_bytes).unwrap();
+        assert_eq!(actual_bytes, expected_bytes);
+        // and back from the binary form
+        let mut bytes = vec![];
+        RecursiveVerificationKey::read(&expected_bytes[..])
+            .unwrap()
+            .write(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, expected_bytes);
+    }
+}
@@ -1,9 +1,10 @@
 #![allow(clippy::needless_range_loop)]
-use crate::{bellman_ce, utils};
+use crate::gate_circuit::GateCircuit;
+use crate::{bellman_ce, plonk, utils};
 use bellman_ce::kate_commitment::{Crs, CrsForMonomialForm};
 use bellman_ce::pairing::bn256;
 use bellman_ce::pairing::bn256::Bn256;
-use bellman_ce::pairing::ff::ScalarEngine;
+use bellman_ce::pairing::ff::{PrimeField, ScalarEngine};
 use bellman_ce::pairing::{CurveAffine, Engine};
 use bellman_ce::plonk::better_better_cs::cs::PlonkCsWidth4WithNextStepAndCustomGatesParams;
 use bellman_ce::plonk::better_better_cs::cs::ProvingAssembly;
@@ -19,38 +20,450 @@ use bellman_ce::plonk::{
 };
 use bellman_ce::worker::Worker;
 use bellman_ce::{Field, SynthesisError};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use franklin_crypto::plonk::circuit::bigint::field::RnsParameters;
 use franklin_crypto::plonk::circuit::verifier_circuit::affine_point_wrapper::aux_data::{AuxData, BN256AuxData};
 use franklin_crypto::plonk::circuit::verifier_circuit::data_structs::IntoLimbedWitness;
 use franklin_crypto::plonk::circuit::Width4WithCustomGates;
 use franklin_crypto::rescue::bn256::Bn256RescueParams;
-use itertools::Itertools;
 use recurisive_vk_codegen::circuit::{
     create_recursive_circuit_setup, create_recursive_circuit_vk_and_setup, create_vks_tree, make_aggregate,
     make_public_input_and_limbed_aggregate, RecursiveAggregationCircuitBn256,
 };
 pub use recurisive_vk_codegen::types::{AggregatedProof, RecursiveVerificationKey};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod buffer;
+mod json;
+pub use buffer::{aggregate_from_bytes, aggregated_proof_from_bytes, AggregationError, AggregationOutput};
+pub use json::aggregated_proof_from_json;
 
 // only support depth<8. different depths don't really make performance different
-const VK_TREE_DEPTH: usize = 7;
+pub const MAX_VK_TREE_DEPTH: usize = 7;
+pub const DEFAULT_VK_TREE_DEPTH: usize = 7;
+
+// parameters of the recursion circuit a recursive vk is exported for, saved next to the vk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecursiveVkMetadata {
+    pub num_proofs_to_check: usize,
+    pub num_inputs: usize,
+    pub vk_tree_depth: usize,
+}
+
+impl RecursiveVkMetadata {
+    // the metadata of `recursive_vk.bin` is saved as `recursive_vk.meta.json`
+    pub fn path_for(vk_file: &str) -> PathBuf {
+        Path::new(vk_file).with_extension("meta.json")
+    }
+
+    // the depth isn't part of the vk, so check an aggregated proof has the shape of the recursion circuit:
+    // the num of proofs and inputs, and vk indexes within the vk tree
+    pub fn check_proof(&self, proof: &AggregatedProof) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(
+            proof.individual_vk_idxs.len() == self.num_proofs_to_check,
+            "the proof aggregates {} proofs, the recursive vk {}",
+            proof.individual_vk_idxs.len(),
+            self.num_proofs_to_check
+        );
+        anyhow::ensure!(
+            proof.individual_num_inputs == self.num_inputs
+                && proof.individual_vk_inputs.len() == self.num_proofs_to_check * self.num_inputs,
+            "the proof has {} inputs per proof, the recursive vk {}",
+            proof.individual_num_inputs,
+            self.num_inputs
+        );
+        if let Some(idx) = proof.individual_vk_idxs.iter().find(|idx| **idx >= 1 << self.vk_tree_depth) {
+            anyhow::bail!("vk index {} is out of the vk tree of depth {}", idx, self.vk_tree_depth);
+        }
+        Ok(())
+    }
+}
+
+// the setup of a recursion circuit, saved to skip recomputing it on every `prove`
+pub struct RecursiveProvingKey {
+    pub num_proofs_to_check: usize,
+    pub num_inputs: usize,
+    pub vk_tree_depth: usize,
+    pub setup: Setup<Bn256, RecursiveAggregationCircuitBn256<'static>>,
+}
+
+impl RecursiveProvingKey {
+    pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_u64::<BigEndian>(self.num_proofs_to_check as u64)?;
+        writer.write_u64::<BigEndian>(self.num_inputs as u64)?;
+        writer.write_u64::<BigEndian>(self.vk_tree_depth as u64)?;
+        self.setup.write(&mut writer)
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let num_proofs_to_check = reader.read_u64::<BigEndian>()? as usize;
+        let num_inputs = reader.read_u64::<BigEndian>()? as usize;
+        let vk_tree_depth = reader.read_u64::<BigEndian>()? as usize;
+        let setup = Setup::read(&mut reader)?;
+        Ok(Self {
+            num_proofs_to_check,
+            num_inputs,
+            vk_tree_depth,
+            setup,
+        })
+    }
+}
+
+// padding of an aggregation batch: the trailing `num_padding` of the `num_proofs` aggregated proofs are dummies,
+// copies of the hex encoded padding proof of the vk at `padding_vk_idx`, kept to pad the real proofs again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AggregationPadding {
+    pub num_proofs: usize,
+    pub num_padding: usize,
+    pub padding_vk_idx: usize,
+    pub padding_proof: String,
+}
+
+impl AggregationPadding {
+    pub fn num_real_proofs(&self) -> usize {
+        self.num_proofs - self.num_padding
+    }
+
+    pub fn is_dummy(&self, proof_idx: usize) -> bool {
+        proof_idx >= self.num_real_proofs()
+    }
+
+    pub fn padding_proof(&self) -> Result<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>, anyhow::Error> {
+        let bytes = utils::decode_hex(&self.padding_proof)?;
+        Ok(OldProof::read(&bytes[..])?)
+    }
+
+    // pad the real proofs of the batch the same way again
+    pub fn pad(
+        &self,
+        old_proofs: &mut Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>,
+        vk_idxs: &mut Vec<usize>,
+    ) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(
+            old_proofs.len() == self.num_real_proofs(),
+            "{} proofs given, {} real proofs were aggregated",
+            old_proofs.len(),
+            self.num_real_proofs()
+        );
+        pad_proofs(
+            old_proofs,
+            vk_idxs,
+            self.num_proofs,
+            Some((self.padding_proof()?, self.padding_vk_idx)),
+        )?;
+        Ok(())
+    }
+}
+
+// magic of the padding written after the bytes of a padded aggregated proof
+const PADDING_MAGIC: &[u8; 8] = b"PLKTPAD1";
+
+// an aggregated proof, with the dummy entries marked if its batch was padded. The padding is written after the
+// proof bytes, which readers of a bare `AggregatedProof` stop before, and as a 6th element of the json arguments
+pub struct PaddedAggregatedProof {
+    pub proof: AggregatedProof,
+    pub padding: Option<AggregationPadding>,
+}
+
+impl PaddedAggregatedProof {
+    pub fn new(proof: AggregatedProof, padding: Option<AggregationPadding>) -> Result<Self, anyhow::Error> {
+        if let Some(padding) = &padding {
+            anyhow::ensure!(
+                padding.num_proofs == proof.individual_vk_idxs.len() && padding.num_padding <= padding.num_proofs,
+                "padding of {} of {} proofs mismatch with the {} aggregated proofs",
+                padding.num_padding,
+                padding.num_proofs,
+                proof.individual_vk_idxs.len()
+            );
+        }
+        Ok(Self { proof, padding })
+    }
+
+    pub fn num_real_proofs(&self) -> usize {
+        self.padding
+            .as_ref()
+            .map_or(self.proof.individual_vk_idxs.len(), AggregationPadding::num_real_proofs)
+    }
+
+    pub fn is_dummy(&self, proof_idx: usize) -> bool {
+        matches!(&self.padding, Some(p) if p.is_dummy(proof_idx))
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.proof.write(&mut writer)?;
+        if let Some(padding) = &self.padding {
+            let bytes = serde_json::to_vec(padding)?;
+            writer.write_all(PADDING_MAGIC)?;
+            writer.write_u64::<BigEndian>(bytes.len() as u64)?;
+            writer.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, anyhow::Error> {
+        let proof = AggregatedProof::read(&mut reader)?;
+        let mut rest = vec![];
+        reader.read_to_end(&mut rest)?;
+        if rest.is_empty() {
+            return Self::new(proof, None);
+        }
+        anyhow::ensure!(rest.starts_with(PADDING_MAGIC), "unexpected data after the aggregated proof");
+        let mut rest = &rest[PADDING_MAGIC.len()..];
+        let len = rest.read_u64::<BigEndian>()? as usize;
+        anyhow::ensure!(rest.len() == len, "padding of the aggregated proof is truncated");
+        Self::new(proof, Some(serde_json::from_slice(rest)?))
+    }
+
+    // the json arguments of `verifyAggregatedProof`, followed by the padding if any
+    pub fn to_json(&self) -> Result<serde_json::Value, anyhow::Error> {
+        let mut value = serde_json::to_value(&self.proof)?;
+        if let Some(padding) = &self.padding {
+            let parts = value
+                .as_array_mut()
+                .ok_or_else(|| anyhow::format_err!("unexpected aggregated proof layout"))?;
+            parts.push(serde_json::to_value(padding)?);
+        }
+        Ok(value)
+    }
+}
+
+// pad a batch to `pad_to` entries by repeating `padding`, and return the num of padded entries
+fn pad_batch<T: Clone>(batch: &mut Vec<T>, pad_to: usize, padding: T) -> Result<usize, anyhow::Error> {
+    anyhow::ensure!(batch.len() <= pad_to, "cannot pad a batch of {} proofs to {}", batch.len(), pad_to);
+    let num_padding = pad_to - batch.len();
+    batch.resize(pad_to, padding);
+    Ok(num_padding)
+}
+
+// pad proofs (and their vk indexes) to `pad_to` proofs with a padding proof, the last proof by default
+pub fn pad_proofs(
+    old_proofs: &mut Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    vk_idxs: &mut Vec<usize>,
+    pad_to: usize,
+    padding: Option<(OldProof<Bn256, PlonkCsWidth4WithNextStepParams>, usize)>,
+) -> Result<AggregationPadding, anyhow::Error> {
+    anyhow::ensure!(!old_proofs.is_empty(), "no proof to pad");
+    anyhow::ensure!(old_proofs.len() == vk_idxs.len(), "vk indexes and proofs mismatch!");
+    let (padding_proof, padding_vk_idx) = match padding {
+        Some(padding) => padding,
+        None => (old_proofs.last().unwrap().clone(), *vk_idxs.last().unwrap()),
+    };
+    anyhow::ensure!(
+        padding_proof.num_inputs == old_proofs[0].num_inputs,
+        "padding proof num_inputs mismatch!"
+    );
+    let mut proof_bytes = vec![];
+    padding_proof.write(&mut proof_bytes)?;
+    pad_batch(vk_idxs, pad_to, padding_vk_idx)?;
+    let num_padding = pad_batch(old_proofs, pad_to, padding_proof)?;
+    Ok(AggregationPadding {
+        num_proofs: pad_to,
+        num_padding,
+        padding_vk_idx,
+        padding_proof: utils::encode_hex(&proof_bytes),
+    })
+}
+
+// prove the dummy circuit with `num_inputs` inputs, to pad a batch with valid proofs instead of copies of a real one.
+// `dummy_vk` is its vk in the vk tree, exported from the same srs. The srs is given back for the recursion circuit
+pub fn prove_dummy(
+    big_crs: Crs<Bn256, CrsForMonomialForm>,
+    num_inputs: usize,
+    dummy_vk: &OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>,
+) -> Result<(OldProof<Bn256, PlonkCsWidth4WithNextStepParams>, Crs<Bn256, CrsForMonomialForm>), anyhow::Error> {
+    let circuit = GateCircuit::<Bn256>::dummy(num_inputs);
+    let setup = plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, big_crs)?;
+    let (mut vk_bytes, mut dummy_vk_bytes) = (vec![], vec![]);
+    setup.make_verification_key()?.write(&mut vk_bytes)?;
+    dummy_vk.write(&mut dummy_vk_bytes)?;
+    anyhow::ensure!(
+        vk_bytes == dummy_vk_bytes,
+        "the padding vk is not the vk of the dummy circuit with {} inputs",
+        num_inputs
+    );
+    let proof = setup.prove_native(&circuit, "rescue")?;
+    let big_crs = Arc::try_unwrap(setup.into_key_monomial_form()).map_err(|_| anyhow::format_err!("the srs is still shared"))?;
+    Ok((proof, big_crs))
+}
+
+// check that the vk tree depth is supported by the recursion circuit, and that the tree can hold all the vks
+pub fn check_vk_tree_depth(vk_tree_depth: usize, num_vks: usize) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(
+        vk_tree_depth > 0 && vk_tree_depth <= MAX_VK_TREE_DEPTH,
+        "vk tree depth must be within 1..={}, got {}",
+        MAX_VK_TREE_DEPTH,
+        vk_tree_depth
+    );
+    anyhow::ensure!(
+        num_vks <= 1 << vk_tree_depth,
+        "{} vks don't fit into a vk tree of depth {}",
+        num_vks,
+        vk_tree_depth
+    );
+    Ok(())
+}
+
+// check the vk index of every proof, and collect the vk of each proof
+fn vks_of_proofs(
+    old_vks: &[OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>],
+    vk_idxs: &[usize],
+    vk_tree_depth: usize,
+) -> Result<Vec<OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>>, anyhow::Error> {
+    anyhow::ensure!(!old_vks.is_empty(), "no vk to aggregate with");
+    check_vk_tree_depth(vk_tree_depth, old_vks.len())?;
+    vk_idxs
+        .iter()
+        .enumerate()
+        .map(|(i, &idx)| {
+            old_vks
+                .get(idx)
+                .cloned()
+                .ok_or_else(|| anyhow::format_err!("proof {}: vk index {} out of range", i, idx))
+        })
+        .collect()
+}
+
+// why an old proof cannot be aggregated
+#[derive(Debug, Clone, PartialEq)]
+pub enum InnerProofError {
+    NumInputsMismatch { expected: usize, actual: usize },
+    // valid, but made with the keccak transcript. The recursion circuit only verifies rescue transcript proofs
+    WrongTranscript,
+    Invalid,
+    VerificationFailed(String),
+}
+
+impl std::fmt::Display for InnerProofError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InnerProofError::NumInputsMismatch { expected, actual } => {
+                write!(f, "num_inputs mismatch, expect {}, got {}", expected, actual)
+            }
+            InnerProofError::WrongTranscript => write!(f, "proof is made with the keccak transcript, use '-t rescue' to prove"),
+            InnerProofError::Invalid => write!(f, "invalid proof"),
+            InnerProofError::VerificationFailed(e) => write!(f, "fail to verify proof: {}", e),
+        }
+    }
+}
+
+fn check_old_proof(
+    proof: &OldProof<Bn256, PlonkCsWidth4WithNextStepParams>,
+    vk: &OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>,
+    num_inputs: usize,
+) -> Result<(), InnerProofError> {
+    if proof.num_inputs != num_inputs {
+        return Err(InnerProofError::NumInputsMismatch {
+            expected: num_inputs,
+            actual: proof.num_inputs,
+        });
+    }
+    if proof.num_inputs != vk.num_inputs {
+        return Err(InnerProofError::NumInputsMismatch {
+            expected: vk.num_inputs,
+            actual: proof.num_inputs,
+        });
+    }
+    match plonk::verify(vk, proof, "rescue") {
+        Ok(true) => Ok(()),
+        Ok(false) => match plonk::verify(vk, proof, "keccak") {
+            Ok(true) => Err(InnerProofError::WrongTranscript),
+            _ => Err(InnerProofError::Invalid),
+        },
+        Err(e) => Err(InnerProofError::VerificationFailed(format!("{:?}", e))),
+    }
+}
+
+// verify every old proof against its vk, and collect the index and reason of every failed proof
+pub fn failed_old_proofs(
+    old_proofs: &[OldProof<Bn256, PlonkCsWidth4WithNextStepParams>],
+    old_vks: &[OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>],
+    vk_idxs: &[usize],
+    vk_tree_depth: usize,
+) -> Result<Vec<(usize, InnerProofError)>, anyhow::Error> {
+    anyhow::ensure!(!old_proofs.is_empty(), "no proof to aggregate");
+    anyhow::ensure!(old_proofs.len() < 256, "too many proofs to aggregate: {}", old_proofs.len());
+    anyhow::ensure!(
+        vk_idxs.len() == old_proofs.len(),
+        "vk indexes and proofs mismatch: {} indexes for {} proofs",
+        vk_idxs.len(),
+        old_proofs.len()
+    );
+    let vks = vks_of_proofs(old_vks, vk_idxs, vk_tree_depth)?;
+    let num_inputs = old_proofs[0].num_inputs;
+    Ok(old_proofs
+        .iter()
+        .zip(vks.iter())
+        .enumerate()
+        .filter_map(|(i, (proof, vk))| check_old_proof(proof, vk, num_inputs).err().map(|e| (i, e)))
+        .collect())
+}
+
+// verify every old proof against its vk before the (expensive) aggregation, reporting all the failed entries
+pub fn check_old_proofs(
+    old_proofs: &[OldProof<Bn256, PlonkCsWidth4WithNextStepParams>],
+    old_vks: &[OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>],
+    vk_idxs: &[usize],
+    vk_tree_depth: usize,
+) -> Result<(), anyhow::Error> {
+    let failures = failed_old_proofs(old_proofs, old_vks, vk_idxs, vk_tree_depth)?;
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "{} of {} proofs cannot be aggregated:\n{}",
+            failures.len(),
+            old_proofs.len(),
+            failures
+                .iter()
+                .map(|(i, e)| format!("proof {}: {}", i, e))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+    Ok(())
+}
 
-// recursively prove multiple proofs, and aggregate them into one
+// recursively prove multiple proofs, and aggregate them into one.
+// `old_vks` are the distinct vks of the aggregated circuits, and `vk_idxs` the index of the vk for each proof.
+// The setup of the recursion circuit is computed unless a proving key is given.
 pub fn prove(
     big_crs: Crs<Bn256, CrsForMonomialForm>,
     old_proofs: Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>,
-    old_vk: OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>,
-) -> Result<AggregatedProof, SynthesisError> {
+    old_vks: Vec<OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    vk_idxs: Vec<usize>,
+    vk_tree_depth: usize,
+    pk: Option<RecursiveProvingKey>,
+) -> Result<AggregatedProof, anyhow::Error> {
+    check_old_proofs(&old_proofs, &old_vks, &vk_idxs, vk_tree_depth)?;
+    prove_unchecked(big_crs, old_proofs, old_vks, vk_idxs, vk_tree_depth, pk)
+}
+
+// `prove` for old proofs that passed `check_old_proofs` already
+fn prove_unchecked(
+    big_crs: Crs<Bn256, CrsForMonomialForm>,
+    old_proofs: Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    old_vks: Vec<OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    vk_idxs: Vec<usize>,
+    vk_tree_depth: usize,
+    pk: Option<RecursiveProvingKey>,
+) -> Result<AggregatedProof, anyhow::Error> {
+    anyhow::ensure!(!old_proofs.is_empty(), "no proof to aggregate");
     let num_proofs_to_check = old_proofs.len();
-    assert!(num_proofs_to_check > 0);
-    assert!(num_proofs_to_check < 256);
-    let mut individual_vk_inputs = Vec::new();
     let num_inputs = old_proofs[0].num_inputs;
-    for p in &old_proofs {
-        for input_value in p.input_values.clone() {
-            individual_vk_inputs.push(input_value);
+    // check the proving key before the costly synthesis
+    let pk_setup = match pk {
+        Some(pk) => {
+            anyhow::ensure!(
+                pk.num_proofs_to_check == num_proofs_to_check,
+                "num of proofs mismatch with the proving key"
+            );
+            anyhow::ensure!(pk.num_inputs == num_inputs, "num_inputs mismatch with the proving key");
+            anyhow::ensure!(pk.vk_tree_depth == vk_tree_depth, "vk tree depth mismatch with the proving key");
+            Some(pk.setup)
         }
-        assert_eq!(p.num_inputs, num_inputs, "proofs num_inputs mismatch!");
-    }
+        None => None,
+    };
+    let individual_vk_inputs: Vec<_> = old_proofs.iter().flat_map(|p| p.input_values.clone()).collect();
 
     let worker = Worker::new();
     let rns_params = RnsParameters::<Bn256, <Bn256 as Engine>::Fq>::new_for_field(68, 110, 4);
@@ -60,22 +473,22 @@ pub fn prove(
     g2_bases.copy_from_slice(&big_crs.g2_monomial_bases.as_ref()[..]);
     let aux_data = BN256AuxData::new();
 
-    //notice we have only 1 vk now
-    let vks = old_proofs.iter().map(|_| old_vk.clone()).collect_vec();
-    let individual_vk_idxs = old_proofs.iter().map(|_| 0usize).collect_vec();
-    let (_, (vks_tree, all_witness_values)) = create_vks_tree(&[old_vk], VK_TREE_DEPTH)?;
+    let vks = vks_of_proofs(&old_vks, &vk_idxs, vk_tree_depth)?;
+    let (_, (vks_tree, all_witness_values)) = create_vks_tree(&old_vks, vk_tree_depth)?;
     let vks_tree_root = vks_tree.get_commitment();
 
-    let proof_ids = individual_vk_idxs.clone();
+    let proof_ids = vk_idxs.clone();
 
     let mut queries = vec![];
     for proof_id in 0..num_proofs_to_check {
-        let vk = &vks[individual_vk_idxs[proof_id]];
+        let vk = &vks[proof_id];
+        let vk_idx = vk_idxs[proof_id];
 
         let leaf_values = vk.into_witness_for_params(&rns_params).expect("must transform into limbed witness");
 
+        // each vk is a leaf of the vk tree
         let values_per_leaf = leaf_values.len();
-        let intra_leaf_indexes_to_query: Vec<_> = ((proof_id * values_per_leaf)..((proof_id + 1) * values_per_leaf)).collect();
+        let intra_leaf_indexes_to_query: Vec<_> = ((vk_idx * values_per_leaf)..((vk_idx + 1) * values_per_leaf)).collect();
         let q = vks_tree.produce_query(intra_leaf_indexes_to_query, &all_witness_values);
 
         assert_eq!(q.values(), &leaf_values[..]);
@@ -90,7 +503,7 @@ pub fn prove(
     let circuit = RecursiveAggregationCircuitBn256 {
         num_proofs_to_check,
         num_inputs,
-        vk_tree_depth: VK_TREE_DEPTH,
+        vk_tree_depth,
         vk_root: Some(vks_tree_root),
         vk_witnesses: Some(vks),
         vk_auth_paths: Some(queries),
@@ -109,19 +522,21 @@ pub fn prove(
 
     // quick_check_if_satisifed
     let mut cs = TrivialAssembly::<Bn256, Width4WithCustomGates, Width4MainGateWithDNext>::new();
-    circuit.synthesize(&mut cs).expect("should synthesize");
+    circuit.synthesize(&mut cs)?;
     log::info!("Raw number of gates: {}", cs.n());
     cs.finalize();
     log::info!("Padded number of gates: {}", cs.n());
-    assert!(cs.is_satisfied());
-    log::info!("satisfied {}", cs.is_satisfied());
-    assert_eq!(cs.num_inputs, 1);
+    // the old proofs are checked already, so this is only a sanity check of the recursion circuit
+    anyhow::ensure!(cs.is_satisfied(), "the recursion circuit is not satisfied");
+    anyhow::ensure!(cs.num_inputs == 1, "the recursion circuit has {} inputs", cs.num_inputs);
 
-    let setup: Setup<Bn256, RecursiveAggregationCircuitBn256> =
-        create_recursive_circuit_setup(num_proofs_to_check, num_inputs, VK_TREE_DEPTH)?;
+    let setup: Setup<Bn256, RecursiveAggregationCircuitBn256> = match pk_setup {
+        Some(setup) => setup,
+        None => create_recursive_circuit_setup(num_proofs_to_check, num_inputs, vk_tree_depth)?,
+    };
 
     let mut assembly = ProvingAssembly::<Bn256, PlonkCsWidth4WithNextStepAndCustomGatesParams, Width4MainGateWithDNext>::new();
-    circuit.synthesize(&mut assembly).expect("must synthesize");
+    circuit.synthesize(&mut assembly)?;
     assembly.finalize();
 
     let proof = assembly.create_proof::<_, RollingKeccakTranscript<<Bn256 as ScalarEngine>::Fr>>(&worker, &setup, &big_crs, None)?;
@@ -130,7 +545,7 @@ pub fn prove(
         proof,
         individual_vk_inputs,
         individual_num_inputs: num_inputs,
-        individual_vk_idxs,
+        individual_vk_idxs: vk_idxs,
         aggr_limbs: limbed_aggreagate,
     })
 }
@@ -196,43 +611,233 @@ pub fn verify(
 pub fn export_vk(
     num_proofs_to_check: usize,
     num_inputs: usize,
+    vk_tree_depth: usize,
     big_crs: &Crs<Bn256, CrsForMonomialForm>,
 ) -> Result<VerificationKey<Bn256, RecursiveAggregationCircuitBn256>, anyhow::Error> {
+    check_vk_tree_depth(vk_tree_depth, 1)?;
     let (recursive_circuit_vk, _recursive_circuit_setup) =
-        create_recursive_circuit_vk_and_setup(num_proofs_to_check, num_inputs, VK_TREE_DEPTH, big_crs)?;
+        create_recursive_circuit_vk_and_setup(num_proofs_to_check, num_inputs, vk_tree_depth, big_crs)?;
     Ok(recursive_circuit_vk)
 }
 
+// export the setup of a recursion circuit, which can be reused by `prove` and `export_vk_from_pk`
+pub fn export_pk(num_proofs_to_check: usize, num_inputs: usize, vk_tree_depth: usize) -> Result<RecursiveProvingKey, anyhow::Error> {
+    check_vk_tree_depth(vk_tree_depth, 1)?;
+    let setup = create_recursive_circuit_setup(num_proofs_to_check, num_inputs, vk_tree_depth)?;
+    Ok(RecursiveProvingKey {
+        num_proofs_to_check,
+        num_inputs,
+        vk_tree_depth,
+        setup,
+    })
+}
+
+// export a verification key for a recursion circuit from its saved setup
+pub fn export_vk_from_pk(
+    pk: &RecursiveProvingKey,
+    big_crs: &Crs<Bn256, CrsForMonomialForm>,
+) -> Result<VerificationKey<Bn256, RecursiveAggregationCircuitBn256<'static>>, anyhow::Error> {
+    let worker = Worker::new();
+    Ok(VerificationKey::from_setup(&pk.setup, &worker, big_crs)?)
+}
+
 // hash the vk_tree root, proof_indexes, proofs' inputs and aggregated points
 pub fn get_aggregated_input(
     old_proofs: Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>,
-    old_vk: OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>,
+    old_vks: Vec<OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    vk_idxs: Vec<usize>,
+    vk_tree_depth: usize,
 ) -> Result<bn256::Fr, anyhow::Error> {
     let num_proofs_to_check = old_proofs.len();
-    assert!(num_proofs_to_check > 0);
-    assert!(num_proofs_to_check < 256);
+    anyhow::ensure!(
+        num_proofs_to_check > 0 && num_proofs_to_check < 256,
+        "cannot aggregate {} proofs",
+        num_proofs_to_check
+    );
+    anyhow::ensure!(vk_idxs.len() == num_proofs_to_check, "vk indexes and proofs mismatch!");
     let num_inputs = old_proofs[0].num_inputs;
-    for p in &old_proofs {
-        assert_eq!(p.num_inputs, num_inputs, "proofs num_inputs mismatch!");
+    if let Some(i) = old_proofs.iter().position(|p| p.num_inputs != num_inputs) {
+        anyhow::bail!(
+            "proof {}: num_inputs mismatch, expect {}, got {}",
+            i,
+            num_inputs,
+            old_proofs[i].num_inputs
+        );
     }
 
     let rns_params = RnsParameters::<Bn256, <Bn256 as Engine>::Fq>::new_for_field(68, 110, 4);
     let rescue_params = Bn256RescueParams::new_checked_2_into_1();
 
-    let vks = old_proofs.iter().map(|_| old_vk.clone()).collect_vec();
-    let proof_ids = (0..num_proofs_to_check).map(|_| 0usize).collect_vec();
+    let vks = vks_of_proofs(&old_vks, &vk_idxs, vk_tree_depth)?;
 
-    let (_, (vks_tree, _)) = create_vks_tree(&[old_vk], VK_TREE_DEPTH)?;
+    let (_, (vks_tree, _)) = create_vks_tree(&old_vks, vk_tree_depth)?;
     let vks_tree_root = vks_tree.get_commitment();
 
     let aggregate = make_aggregate(&old_proofs, &vks, &rescue_params, &rns_params)?;
 
-    let (expected_input, _) = make_public_input_and_limbed_aggregate(vks_tree_root, &proof_ids, &old_proofs, &aggregate, &rns_params);
+    let (expected_input, _) = make_public_input_and_limbed_aggregate(vks_tree_root, &vk_idxs, &old_proofs, &aggregate, &rns_params);
 
     Ok(expected_input)
 }
 
-pub fn get_vk_tree_root_hash(old_vk: OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>) -> Result<bn256::Fr, anyhow::Error> {
-    let (_, (vks_tree, _)) = create_vks_tree(&vec![old_vk], VK_TREE_DEPTH)?;
+// how an aggregated proof matches one of the original proofs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AggregatedProofCheck {
+    pub index: usize,
+    pub inputs_match: bool,
+    pub expected_vk_idx: Option<usize>,
+    pub actual_vk_idx: Option<usize>,
+}
+
+impl AggregatedProofCheck {
+    pub fn is_valid(&self) -> bool {
+        self.inputs_match && self.expected_vk_idx.is_some() && self.expected_vk_idx == self.actual_vk_idx
+    }
+}
+
+// result of checking an aggregated proof against the original proofs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AggregationCheckReport {
+    pub expected_input: String,
+    pub actual_input: String,
+    pub input_match: bool,
+    // the proofs of the batch that aren't dummies, and whether the proof list has as many. Only they are checked one by one
+    pub num_real_proofs: usize,
+    pub num_proofs_match: bool,
+    pub proofs: Vec<AggregatedProofCheck>,
+}
+
+impl AggregationCheckReport {
+    pub fn is_valid(&self) -> bool {
+        self.input_match && self.num_proofs_match && self.proofs.iter().all(AggregatedProofCheck::is_valid)
+    }
+}
+
+// check an aggregated proof is corresponding to the original proofs: it must carry their inputs and vk indexes,
+// skipping the dummy entries, and its public input must be the hash of them (padded again if the batch was padded)
+pub fn check_aggregation(
+    mut old_proofs: Vec<OldProof<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    old_vks: Vec<OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>>,
+    mut vk_idxs: Vec<usize>,
+    vk_tree_depth: usize,
+    padded_proof: &PaddedAggregatedProof,
+) -> Result<AggregationCheckReport, anyhow::Error> {
+    let aggregated_proof = &padded_proof.proof;
+    anyhow::ensure!(
+        aggregated_proof.individual_num_inputs > 0,
+        "aggregated proof has no individual inputs"
+    );
+    let actual_inputs = aggregated_proof
+        .individual_vk_inputs
+        .chunks(aggregated_proof.individual_num_inputs)
+        .collect::<Vec<_>>();
+    let num_real_proofs = padded_proof.num_real_proofs();
+    let num_proofs_match = old_proofs.len() == num_real_proofs;
+    let proofs = (0..old_proofs.len().max(num_real_proofs))
+        .map(|i| AggregatedProofCheck {
+            index: i,
+            inputs_match: match (old_proofs.get(i), actual_inputs.get(i)) {
+                (Some(old_proof), Some(actual)) => old_proof.input_values[..] == actual[..],
+                _ => false,
+            },
+            expected_vk_idx: vk_idxs.get(i).copied(),
+            actual_vk_idx: aggregated_proof.individual_vk_idxs.get(i).copied(),
+        })
+        .collect();
+
+    // the input hashes the whole batch, the proof list only has the real proofs. A mismatching one is hashed as is and fails
+    if let Some(padding) = padded_proof.padding.as_ref().filter(|_| num_proofs_match) {
+        padding.pad(&mut old_proofs, &mut vk_idxs)?;
+    }
+    let expected = get_aggregated_input(old_proofs, old_vks, vk_idxs, vk_tree_depth)?;
+    let actual = aggregated_proof
+        .proof
+        .inputs
+        .first()
+        .copied()
+        .ok_or_else(|| anyhow::format_err!("aggregated proof has no input"))?;
+    Ok(AggregationCheckReport {
+        expected_input: utils::repr_to_big(expected.into_repr()),
+        actual_input: utils::repr_to_big(actual.into_repr()),
+        input_match: expected == actual,
+        num_real_proofs,
+        num_proofs_match,
+        proofs,
+    })
+}
+
+pub fn get_vk_tree_root_hash(
+    old_vks: &[OldVerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>],
+    vk_tree_depth: usize,
+) -> Result<bn256::Fr, anyhow::Error> {
+    check_vk_tree_depth(vk_tree_depth, old_vks.len())?;
+    let (_, (vks_tree, _)) = create_vks_tree(old_vks, vk_tree_depth)?;
     Ok(vks_tree.get_commitment())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_vk_tree_depth() {
+        assert!(check_vk_tree_depth(DEFAULT_VK_TREE_DEPTH, 1).is_ok());
+        assert!(check_vk_tree_depth(2, 4).is_ok());
+        assert!(check_vk_tree_depth(2, 5).is_err());
+        assert!(check_vk_tree_depth(0, 1).is_err());
+        assert!(check_vk_tree_depth(MAX_VK_TREE_DEPTH + 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_pad_batch() {
+        let mut batch = vec![1, 2, 3];
+        assert_eq!(pad_batch(&mut batch, 5, 0).unwrap(), 2);
+        assert_eq!(batch, vec![1, 2, 3, 0, 0]);
+        assert!(pad_batch(&mut batch, 4, 0).is_err());
+        let padding = AggregationPadding {
+            num_proofs: 5,
+            num_padding: 2,
+            padding_vk_idx: 0,
+            padding_proof: String::new(),
+        };
+        assert_eq!(padding.num_real_proofs(), 3);
+        assert!(!padding.is_dummy(2));
+        assert!(padding.is_dummy(3));
+    }
+
+    #[test]
+    fn test_aggregation_check_report() {
+        let check = AggregatedProofCheck {
+            index: 0,
+            inputs_match: true,
+            expected_vk_idx: Some(1),
+            actual_vk_idx: Some(1),
+        };
+        let mut report = AggregationCheckReport {
+            expected_input: "1".to_owned(),
+            actual_input: "1".to_owned(),
+            input_match: true,
+            num_real_proofs: 1,
+            num_proofs_match: true,
+            proofs: vec![check.clone()],
+        };
+        assert!(report.is_valid());
+        report.num_proofs_match = false;
+        assert!(!report.is_valid());
+        report.num_proofs_match = true;
+        report.proofs.push(AggregatedProofCheck {
+            index: 1,
+            expected_vk_idx: None,
+            ..check
+        });
+        assert!(!report.is_valid());
+    }
+
+    #[test]
+    fn test_metadata_path() {
+        assert_eq!(
+            RecursiveVkMetadata::path_for("keys/recursive_vk.bin"),
+            PathBuf::from("keys/recursive_vk.meta.json")
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// generate a standalone rust crate, which verifies proofs of one circuit with the vk baked in
+use crate::bellman_ce::pairing::bn256::{Bn256, Fr, G1Affine, G2Affine};
+use crate::bellman_ce::pairing::{ff::PrimeField, CurveAffine};
+use crate::bellman_ce::plonk::{better_cs::cs::PlonkCsWidth4WithNextStepParams, better_cs::keys::VerificationKey, domains::Domain};
+use itertools::Itertools;
+use std::fs;
+use std::path::Path;
+
+const CARGO_TEMPLATE: &str = include_str!("template/Cargo.toml.tpl");
+const LIB_TEMPLATE: &str = include_str!("template/lib.rs.tpl");
+const VERIFIER_SOURCE: &str = include_str!("../verifier.rs");
+
+// the template verifier itself, for the tests
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../verifier.rs"]
+pub(crate) mod template;
+
+const ZERO_HEX: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+fn fe_to_hex<F: PrimeField>(fe: &F) -> String {
+    format!("{}", fe.into_repr())
+}
+
+// the point at infinity is encoded as (0, 0)
+fn g1_to_hex(point: &G1Affine) -> [String; 2] {
+    if point.is_zero() {
+        return [ZERO_HEX.to_owned(), ZERO_HEX.to_owned()];
+    }
+    let (x, y) = point.into_xy_unchecked();
+    [fe_to_hex(&x), fe_to_hex(&y)]
+}
+
+fn g2_to_hex(point: &G2Affine) -> [String; 4] {
+    let (x, y) = point.into_xy_unchecked();
+    [fe_to_hex(&x.c0), fe_to_hex(&x.c1), fe_to_hex(&y.c0), fe_to_hex(&y.c1)]
+}
+
+fn render_g1_list(points: &[G1Affine]) -> String {
+    points
+        .iter()
+        .map(|p| {
+            let [x, y] = g1_to_hex(p);
+            format!("    [\"{}\", \"{}\"],\n", x, y)
+        })
+        .join("")
+}
+
+// render the content of the generated lib.rs
+pub fn render_lib(vk: &VerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>) -> Result<String, anyhow::Error> {
+    anyhow::ensure!(vk.selector_commitments.len() == 6, "unexpected number of selector commitments");
+    anyhow::ensure!(
+        vk.next_step_selector_commitments.len() == 1,
+        "unexpected number of next step selector commitments"
+    );
+    anyhow::ensure!(
+        vk.permutation_commitments.len() == 4,
+        "unexpected number of permutation commitments"
+    );
+    anyhow::ensure!(vk.non_residues.len() == 3, "unexpected number of non-residues");
+
+    let domain_size = vk.n + 1;
+    anyhow::ensure!(domain_size.is_power_of_two(), "domain size is not a power of two");
+    let domain = Domain::<Fr>::new_for_size(domain_size as u64)?;
+
+    let non_residues = vk.non_residues.iter().map(|r| format!("    \"{}\",\n", fe_to_hex(r))).join("");
+    let g2_elements = vk
+        .g2_elements
+        .iter()
+        .map(|p| {
+            let [x_c0, x_c1, y_c0, y_c1] = g2_to_hex(p);
+            format!("    [\"{}\", \"{}\", \"{}\", \"{}\"],\n", x_c0, x_c1, y_c0, y_c1)
+        })
+        .join("");
+
+    Ok(LIB_TEMPLATE
+        .replace("{{domain_size}}", &domain_size.to_string())
+        .replace("{{num_inputs}}", &vk.num_inputs.to_string())
+        .replace("{{omega}}", &fe_to_hex(&domain.generator))
+        .replace("{{selector_commitments}}", &render_g1_list(&vk.selector_commitments))
+        .replace(
+            "{{next_step_selector_commitments}}",
+            &render_g1_list(&vk.next_step_selector_commitments),
+        )
+        .replace("{{permutation_commitments}}", &render_g1_list(&vk.permutation_commitments))
+        .replace("{{non_residues}}", &non_residues)
+        .replace("{{g2_elements}}", &g2_elements))
+}
+
+// render a verifier crate named `crate_name` into the `output_dir` directory
+pub fn render_verifier_crate(
+    vk: &VerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>,
+    crate_name: &str,
+    output_dir: &str,
+) -> Result<(), anyhow::Error> {
+    let lib = render_lib(vk)?;
+    let src_dir = Path::new(output_dir).join("src");
+    fs::create_dir_all(&src_dir)?;
+    fs::write(
+        Path::new(output_dir).join("Cargo.toml"),
+        CARGO_TEMPLATE.replace("{{crate_name}}", crate_name),
+    )?;
+    fs::write(src_dir.join("lib.rs"), lib)?;
+    fs::write(src_dir.join("verifier.rs"), VERIFIER_SOURCE)?;
+    Ok(())
+}
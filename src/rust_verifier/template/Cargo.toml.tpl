@@ -0,0 +1,11 @@
+[package]
+name = "{{crate_name}}"
+version = "0.1.0"
+description = "Plonk verifier generated by plonkit"
+edition = "2021"
+
+[dependencies]
+ark-bn254 = { version = "0.3", default-features = false, features = [ "curve" ] }
+ark-ec = { version = "0.3", default-features = false }
+ark-ff = { version = "0.3", default-features = false }
+tiny-keccak = { version = "2.0", features = [ "keccak" ] }
@@ -0,0 +1,78 @@
+//! A plonk verifier for a single circuit, generated by plonkit from its verification key.
+#![no_std]
+extern crate alloc;
+
+pub mod verifier;
+
+pub use ark_bn254::Fr;
+pub use verifier::{Proof, VerificationKey};
+
+const DOMAIN_SIZE: usize = {{domain_size}};
+const NUM_INPUTS: usize = {{num_inputs}};
+const OMEGA: &str = "{{omega}}";
+const SELECTOR_COMMITMENTS: [[&str; 2]; 6] = [
+{{selector_commitments}}];
+const NEXT_STEP_SELECTOR_COMMITMENTS: [[&str; 2]; 1] = [
+{{next_step_selector_commitments}}];
+const PERMUTATION_COMMITMENTS: [[&str; 2]; 4] = [
+{{permutation_commitments}}];
+const NON_RESIDUES: [&str; 3] = [
+{{non_residues}}];
+// [x.c0, x.c1, y.c0, y.c1]
+const G2_ELEMENTS: [[&str; 4]; 2] = [
+{{g2_elements}}];
+
+fn g1(coordinates: &[&str; 2]) -> ark_bn254::G1Affine {
+    verifier::new_g1(
+        verifier::fq_from_hex(coordinates[0]).unwrap(),
+        verifier::fq_from_hex(coordinates[1]).unwrap(),
+    )
+    .expect("embedded G1 point must be valid")
+}
+
+fn g2(coordinates: &[&str; 4]) -> ark_bn254::G2Affine {
+    let fq = |i: usize| verifier::fq_from_hex(coordinates[i]).unwrap();
+    verifier::new_g2(ark_bn254::Fq2::new(fq(0), fq(1)), ark_bn254::Fq2::new(fq(2), fq(3))).expect("embedded G2 point must be valid")
+}
+
+/// The verification key this crate was generated from
+pub fn verification_key() -> VerificationKey {
+    VerificationKey {
+        n: DOMAIN_SIZE - 1,
+        num_inputs: NUM_INPUTS,
+        omega: verifier::fr_from_hex(OMEGA).unwrap(),
+        selector_commitments: [
+            g1(&SELECTOR_COMMITMENTS[0]),
+            g1(&SELECTOR_COMMITMENTS[1]),
+            g1(&SELECTOR_COMMITMENTS[2]),
+            g1(&SELECTOR_COMMITMENTS[3]),
+            g1(&SELECTOR_COMMITMENTS[4]),
+            g1(&SELECTOR_COMMITMENTS[5]),
+        ],
+        next_step_selector_commitments: [g1(&NEXT_STEP_SELECTOR_COMMITMENTS[0])],
+        permutation_commitments: [
+            g1(&PERMUTATION_COMMITMENTS[0]),
+            g1(&PERMUTATION_COMMITMENTS[1]),
+            g1(&PERMUTATION_COMMITMENTS[2]),
+            g1(&PERMUTATION_COMMITMENTS[3]),
+        ],
+        non_residues: [
+            verifier::fr_from_hex(NON_RESIDUES[0]).unwrap(),
+            verifier::fr_from_hex(NON_RESIDUES[1]).unwrap(),
+            verifier::fr_from_hex(NON_RESIDUES[2]).unwrap(),
+        ],
+        g2_elements: [g2(&G2_ELEMENTS[0]), g2(&G2_ELEMENTS[1])],
+    }
+}
+
+/// Verify a proof (the content of proof.bin written by `plonkit prove`) for the given public inputs
+pub fn verify(proof_bytes: &[u8], public_inputs: &[Fr]) -> bool {
+    let proof = match Proof::read(proof_bytes) {
+        Some(proof) => proof,
+        None => return false,
+    };
+    if proof.input_values[..] != public_inputs[..] {
+        return false;
+    }
+    verifier::verify(&verification_key(), &proof)
+}
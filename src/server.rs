@@ -0,0 +1,431 @@
+// a local http proving service, which keeps the circuit setups in memory between proofs.
+//
+//   GET  /circuits                    hashes of the loaded circuits
+//   POST /circuits/{hash}/prove       submit a witness (wtns bin or json), returns a job id
+//   POST /circuits/{hash}/verify      verify a proof.bin against the circuit vk
+//   GET  /jobs/{id}                   job status
+//   GET  /jobs/{id}/proof.bin         the proof of a done job, also proof.json and public.json
+use crate::bellman_ce::pairing::bn256::{Bn256, Fr};
+use crate::bellman_ce::plonk::{
+    better_cs::cs::PlonkCsWidth4WithNextStepParams,
+    better_cs::keys::{Proof, VerificationKey},
+};
+use crate::circom_circuit::{CircomCircuit, R1CS};
+use crate::{plonk, reader};
+use anyhow::format_err;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver, SendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tiny_keccak::{Hasher, Keccak};
+
+pub struct ServerConfig {
+    // address to listen on, e.g. 127.0.0.1:8080
+    pub addr: String,
+    pub srs_monomial_form: String,
+    pub srs_lagrange_form: Option<String>,
+    // circuit files (r1cs or json) to load at start
+    pub circuits: Vec<String>,
+    pub transcript: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Proving,
+    Done,
+    Failed { error: String },
+}
+
+struct Job {
+    status: JobStatus,
+    proof: Option<Vec<u8>>,
+    proof_json: Option<serde_json::Value>,
+    public_json: Option<serde_json::Value>,
+}
+
+impl Job {
+    fn is_finished(&self) -> bool {
+        matches!(self.status, JobStatus::Done | JobStatus::Failed { .. })
+    }
+}
+
+// finished jobs kept for their results, the oldest ones are evicted first
+const MAX_FINISHED_JOBS: usize = 256;
+
+// a witness json takes at most 80 bytes per value (78 digits, quotes and a comma), more than its bin form
+const MAX_WITNESS_VALUE_SIZE: usize = 80;
+// room for the wtns header or the json brackets and spaces
+const MAX_WITNESS_OVERHEAD: usize = 1 << 16;
+// a proof bin is a few KB, whatever the circuit
+const MAX_PROOF_SIZE: usize = 1 << 16;
+
+// what the http thread knows about a circuit, the setups themselves are owned by the prover thread
+struct CircuitInfo {
+    vk: VerificationKey<Bn256, PlonkCsWidth4WithNextStepParams>,
+    num_variables: usize,
+}
+
+struct ProveTask {
+    job_id: u64,
+    circuit: String,
+    witness: Vec<Fr>,
+}
+
+// a proof to verify, the verifier answers the request itself
+struct VerifyTask {
+    circuit: String,
+    proof: Vec<u8>,
+    request: Request,
+}
+
+type Jobs = Arc<Mutex<HashMap<u64, Job>>>;
+
+// drop the oldest finished jobs beyond MAX_FINISHED_JOBS, job ids being increasing
+fn evict_finished_jobs(jobs: &mut HashMap<u64, Job>) {
+    let mut finished: Vec<u64> = jobs.iter().filter(|(_, job)| job.is_finished()).map(|(id, _)| *id).collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort_unstable();
+    for id in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+        jobs.remove(id);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Route {
+    Circuits,
+    Prove(String),
+    Verify(String),
+    JobStatus(u64),
+    JobFile(u64, String),
+    NotFound,
+}
+
+fn route(method: &Method, url: &str) -> Route {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    match (method, segments.as_slice()) {
+        (Method::Get, ["circuits"]) => Route::Circuits,
+        (Method::Post, ["circuits", hash, "prove"]) => Route::Prove(hash.to_string()),
+        (Method::Post, ["circuits", hash, "verify"]) => Route::Verify(hash.to_string()),
+        (Method::Get, ["jobs", id]) => id.parse().map_or(Route::NotFound, Route::JobStatus),
+        (Method::Get, ["jobs", id, file]) => id.parse().map_or(Route::NotFound, |id| Route::JobFile(id, file.to_string())),
+        _ => Route::NotFound,
+    }
+}
+
+// circuits are keyed by the keccak256 hash of their file
+fn circuit_hash(content: &[u8]) -> String {
+    let mut hasher = Keccak::v256();
+    hasher.update(content);
+    let mut hash = [0u8; 32];
+    hasher.finalize(&mut hash);
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn json_response(status: u16, value: &serde_json::Value) -> Response<Cursor<Vec<u8>>> {
+    let header = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    Response::from_string(value.to_string())
+        .with_status_code(status)
+        .with_header(header)
+}
+
+fn error_response(status: u16, error: &str) -> Response<Cursor<Vec<u8>>> {
+    json_response(status, &serde_json::json!({ "error": error }))
+}
+
+// prove the queued tasks one by one, the setups stay in this thread
+fn run_prover(setups: HashMap<String, (R1CS<Bn256>, plonk::SetupForProver)>, transcript: String, tasks: Receiver<ProveTask>, jobs: Jobs) {
+    let set_status = |job_id, status| {
+        if let Some(job) = jobs.lock().unwrap().get_mut(&job_id) {
+            job.status = status;
+        }
+    };
+    for task in tasks {
+        set_status(task.job_id, JobStatus::Proving);
+        let (r1cs, setup) = &setups[&task.circuit];
+        let circuit = CircomCircuit {
+            r1cs: r1cs.clone(),
+            witness: Some(task.witness),
+            wire_mapping: None,
+            aux_offset: plonk::AUX_OFFSET,
+            constraint_components: None,
+        };
+        // a bad witness makes the prover panic, keep serving the other jobs
+        let result = catch_unwind(AssertUnwindSafe(
+            || -> Result<Proof<Bn256, PlonkCsWidth4WithNextStepParams>, anyhow::Error> {
+                setup
+                    .validate_witness(circuit.clone())
+                    .map_err(|e| format_err!("witness is not satisfied: {:?}", e))?;
+                setup.prove(circuit, &transcript).map_err(|e| format_err!("prove err: {:?}", e))
+            },
+        ))
+        .unwrap_or_else(|_| Err(format_err!("prover panicked")));
+
+        let result = result.and_then(|proof| {
+            let mut buf = vec![];
+            proof.write(&mut buf)?;
+            let (inputs, serialized_proof) = bellman_vk_codegen::serialize_proof(&proof);
+            Ok((buf, serde_json::to_value(&serialized_proof)?, serde_json::to_value(&inputs)?))
+        });
+
+        let mut jobs = jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&task.job_id) {
+            match result {
+                Ok((proof, proof_json, public_json)) => {
+                    job.proof = Some(proof);
+                    job.proof_json = Some(proof_json);
+                    job.public_json = Some(public_json);
+                    job.status = JobStatus::Done;
+                    log::info!("job {} done", task.job_id);
+                }
+                Err(e) => {
+                    log::error!("job {} failed: {}", task.job_id, e);
+                    job.status = JobStatus::Failed { error: e.to_string() };
+                }
+            }
+        }
+        evict_finished_jobs(&mut jobs);
+    }
+}
+
+fn respond(request: Request, response: Response<Cursor<Vec<u8>>>) {
+    if let Err(e) = request.respond(response) {
+        log::error!("fail to respond: {}", e);
+    }
+}
+
+// verify the proofs one by one, off the http thread like the proving
+fn run_verifier(circuits: Arc<HashMap<String, CircuitInfo>>, transcript: String, tasks: Receiver<VerifyTask>) {
+    for task in tasks {
+        let info = &circuits[&task.circuit];
+        let response = match Proof::<Bn256, PlonkCsWidth4WithNextStepParams>::read(task.proof.as_slice()) {
+            // a malformed proof can make the verifier panic, keep serving the other requests
+            Ok(proof) => match catch_unwind(AssertUnwindSafe(|| plonk::verify(&info.vk, &proof, &transcript))) {
+                Ok(Ok(valid)) => json_response(200, &serde_json::json!({ "valid": valid })),
+                Ok(Err(e)) => error_response(400, &format!("fail to verify proof: {:?}", e)),
+                Err(_) => error_response(400, "verifier panicked"),
+            },
+            Err(e) => error_response(400, &format!("invalid proof: {}", e)),
+        };
+        respond(task.request, response);
+    }
+}
+
+// load the circuits and their setups, then serve requests until the process is killed
+pub fn serve(config: ServerConfig) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(!config.circuits.is_empty(), "no circuit to serve");
+    // the srs are loaded once and shared by all the setups, the lagrange form one by the circuits of its size only
+    let key_monomial_form = Arc::new(reader::load_key_monomial_form(&config.srs_monomial_form));
+    let key_lagrange_form = reader::maybe_load_key_lagrange_form(config.srs_lagrange_form.clone()).map(Arc::new);
+    let mut setups = HashMap::new();
+    let mut circuits = HashMap::new();
+    for circuit_file in &config.circuits {
+        let hash = circuit_hash(&std::fs::read(circuit_file)?);
+        log::info!("Loading circuit {} from {}...", hash, circuit_file);
+        let r1cs = reader::load_r1cs(circuit_file);
+        let circuit = CircomCircuit {
+            r1cs: r1cs.clone(),
+            witness: None,
+            wire_mapping: None,
+            aux_offset: plonk::AUX_OFFSET,
+            constraint_components: None,
+        };
+        let mut setup = plonk::SetupForProver::prepare_setup_for_prover(circuit, key_monomial_form.clone(), None)?;
+        match &key_lagrange_form {
+            Some(key_lagrange_form) if key_lagrange_form.g1_bases.len() == setup.domain_size() => {
+                setup.set_key_lagrange_form(Some(key_lagrange_form.clone()));
+            }
+            Some(key_lagrange_form) => log::warn!(
+                "the lagrange form srs of size {} doesn't fit circuit {} of size {}, proving with the monomial form one",
+                key_lagrange_form.g1_bases.len(),
+                hash,
+                setup.domain_size()
+            ),
+            None => {}
+        }
+        let vk = setup.make_verification_key()?;
+        circuits.insert(
+            hash.clone(),
+            CircuitInfo {
+                vk,
+                num_variables: r1cs.num_variables,
+            },
+        );
+        setups.insert(hash, (r1cs, setup));
+    }
+
+    let circuits = Arc::new(circuits);
+    let jobs: Jobs = Arc::new(Mutex::new(HashMap::new()));
+    let (sender, receiver) = channel();
+    let prover_jobs = jobs.clone();
+    let transcript = config.transcript.clone();
+    thread::spawn(move || run_prover(setups, transcript, receiver, prover_jobs));
+    let (verify_sender, verify_receiver) = channel();
+    let verifier_circuits = circuits.clone();
+    let transcript = config.transcript.clone();
+    thread::spawn(move || run_verifier(verifier_circuits, transcript, verify_receiver));
+
+    let server = Server::http(&config.addr).map_err(|e| format_err!("fail to listen on {}: {}", config.addr, e))?;
+    log::info!("Serving {} circuits on http://{}", circuits.len(), config.addr);
+    let mut next_job_id = 0u64;
+    for mut request in server.incoming_requests() {
+        let response = match route(request.method(), request.url()) {
+            Route::Circuits => json_response(200, &serde_json::json!(circuits.keys().collect::<Vec<_>>())),
+            Route::Prove(hash) => match circuits
+                .get(&hash)
+                .map(|info| (info, read_body(&mut request, max_witness_size(info))))
+            {
+                None => error_response(404, "unknown circuit"),
+                Some((_, Err(response))) => response,
+                Some((info, Ok(body))) => match reader::load_witness_from_bytes::<Bn256>(&body) {
+                    Ok(witness) if witness.len() >= info.num_variables => {
+                        let job_id = next_job_id;
+                        next_job_id += 1;
+                        jobs.lock().unwrap().insert(
+                            job_id,
+                            Job {
+                                status: JobStatus::Queued,
+                                proof: None,
+                                proof_json: None,
+                                public_json: None,
+                            },
+                        );
+                        let task = ProveTask {
+                            job_id,
+                            circuit: hash,
+                            witness,
+                        };
+                        match sender.send(task) {
+                            Ok(()) => {
+                                log::info!("job {} queued", job_id);
+                                json_response(202, &serde_json::json!({ "job": job_id }))
+                            }
+                            // the prover thread is gone, e.g. after a panic outside of proving
+                            Err(e) => {
+                                jobs.lock().unwrap().remove(&job_id);
+                                log::error!("fail to queue job {}: {}", job_id, e);
+                                error_response(500, "the prover is not running")
+                            }
+                        }
+                    }
+                    Ok(witness) => error_response(
+                        400,
+                        &format!("witness too short, expect {} values, got {}", info.num_variables, witness.len()),
+                    ),
+                    Err(e) => error_response(400, &format!("invalid witness: {}", e)),
+                },
+            },
+            Route::Verify(hash) if !circuits.contains_key(&hash) => error_response(404, "unknown circuit"),
+            Route::Verify(hash) => match read_body(&mut request, MAX_PROOF_SIZE) {
+                Err(response) => response,
+                Ok(proof) => {
+                    let task = VerifyTask {
+                        circuit: hash,
+                        proof,
+                        request,
+                    };
+                    // the verifier thread is gone, e.g. after a panic outside of verifying
+                    if let Err(SendError(task)) = verify_sender.send(task) {
+                        log::error!("fail to queue a proof to verify");
+                        respond(task.request, error_response(500, "the verifier is not running"));
+                    }
+                    continue;
+                }
+            },
+            Route::JobStatus(id) => match jobs.lock().unwrap().get(&id) {
+                Some(job) => match serde_json::to_value(&job.status) {
+                    Ok(status) => json_response(200, &status),
+                    Err(e) => error_response(500, &e.to_string()),
+                },
+                None => error_response(404, "unknown job"),
+            },
+            Route::JobFile(id, file) => match jobs.lock().unwrap().get(&id) {
+                None => error_response(404, "unknown job"),
+                Some(job) => match (file.as_str(), &job.proof, &job.proof_json, &job.public_json) {
+                    ("proof.bin", Some(proof), _, _) => Response::from_data(proof.clone()),
+                    ("proof.json", _, Some(proof_json), _) => json_response(200, proof_json),
+                    ("public.json", _, _, Some(public_json)) => json_response(200, public_json),
+                    ("proof.bin", ..) | ("proof.json", ..) | ("public.json", ..) => error_response(409, "job is not done"),
+                    _ => error_response(404, "unknown file"),
+                },
+            },
+            Route::NotFound => error_response(404, "not found"),
+        };
+        respond(request, response);
+    }
+    Ok(())
+}
+
+// the largest witness of a circuit, in either format
+fn max_witness_size(info: &CircuitInfo) -> usize {
+    info.num_variables * MAX_WITNESS_VALUE_SIZE + MAX_WITNESS_OVERHEAD
+}
+
+// read the body of a request up to `limit` bytes, a bigger one is refused whether its Content-Length says so or not
+fn read_body(request: &mut Request, limit: usize) -> Result<Vec<u8>, Response<Cursor<Vec<u8>>>> {
+    let too_large = || error_response(413, &format!("request body is larger than {} bytes", limit));
+    if request.body_length().map_or(false, |len| len > limit) {
+        return Err(too_large());
+    }
+    let mut body = vec![];
+    if let Err(e) = request.as_reader().take(limit as u64 + 1).read_to_end(&mut body) {
+        return Err(error_response(400, &format!("fail to read request body: {}", e)));
+    }
+    if body.len() > limit {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route(&Method::Get, "/circuits"), Route::Circuits);
+        assert_eq!(route(&Method::Post, "/circuits/ab12/prove"), Route::Prove("ab12".to_owned()));
+        assert_eq!(route(&Method::Post, "/circuits/ab12/verify/"), Route::Verify("ab12".to_owned()));
+        assert_eq!(route(&Method::Get, "/jobs/3?wait=1"), Route::JobStatus(3));
+        assert_eq!(route(&Method::Get, "/jobs/3/proof.bin"), Route::JobFile(3, "proof.bin".to_owned()));
+        assert_eq!(route(&Method::Get, "/jobs/x"), Route::NotFound);
+        assert_eq!(route(&Method::Get, "/circuits/ab12/prove"), Route::NotFound);
+    }
+
+    #[test]
+    fn test_evict_finished_jobs() {
+        let job = |status| Job {
+            status,
+            proof: None,
+            proof_json: None,
+            public_json: None,
+        };
+        let mut jobs = HashMap::new();
+        for id in 0..MAX_FINISHED_JOBS as u64 + 2 {
+            jobs.insert(id, job(JobStatus::Done));
+        }
+        jobs.insert(0, job(JobStatus::Proving));
+        jobs.insert(1000, job(JobStatus::Queued));
+        evict_finished_jobs(&mut jobs);
+        // the oldest finished job is evicted, the unfinished ones are kept
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 2);
+        assert!(jobs.contains_key(&0) && !jobs.contains_key(&1) && jobs.contains_key(&2));
+        assert!(jobs.contains_key(&1000));
+    }
+
+    #[test]
+    fn test_job_status_json() {
+        assert_eq!(serde_json::to_string(&JobStatus::Queued).unwrap(), r#"{"status":"queued"}"#);
+        assert_eq!(
+            serde_json::to_string(&JobStatus::Failed { error: "e".to_owned() }).unwrap(),
+            r#"{"status":"failed","error":"e"}"#
+        );
+    }
+}
@@ -8,10 +8,12 @@ const CIRCUIT_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/ci
 const WITNESS_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/simple/witness.json");
 const VK_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/simple/vk.bin");
 const PROOF_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/simple/proof.bin");
+const NATIVE_CIRCUIT_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/native/circuit.plonk.json");
+const NATIVE_WITNESS_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/native/witness.json");
 const MONOMIAL_KEY_FILE: &'static str = concat!(env!("CARGO_MANIFEST_DIR"), "/keys/setup/setup_2^10.key");
 const DEFAULT_TRANSCRIPT: &'static str = "keccak";
 
-const CIRCUIT_ANALYZE_RESULT: &'static str = r#"{"num_inputs":2,"num_aux":2,"num_variables":4,"num_constraints":2,"num_nontrivial_constraints":2,"num_gates":3,"num_hints":2,"constraint_stats":[{"name":"0","num_gates":1},{"name":"1","num_gates":2}]}"#;
+const CIRCUIT_ANALYZE_RESULT: &'static str = r#"{"num_inputs":2,"num_aux":2,"num_variables":4,"num_constraints":2,"num_nontrivial_constraints":2,"num_gates":3,"num_hints":2,"constraint_stats":[{"name":"0","num_gates":1},{"name":"1","num_gates":2}],"shape_histogram":[{"key":"multiplication a1 b1 c2","num_constraints":1,"num_gates":2},{"key":"multiplication a1 b1 c1","num_constraints":1,"num_gates":1}],"variant_histogram":[{"key":"multiplication_gate","num_constraints":2,"num_gates":3}],"most_expensive_constraints":[{"name":"1","num_gates":2,"shape":"multiplication a1 b1 c2","variant":"multiplication_gate"},{"name":"0","num_gates":1,"shape":"multiplication a1 b1 c1","variant":"multiplication_gate"}],"resources":{"domain_power":2,"srs_power":10,"srs_monomial_form":"setup_2^10.key","prove_memory_bytes":85376,"prove_secs":null,"dump_lagrange_memory_bytes":74400,"dump_lagrange_secs":null}}"#;
 
 #[test]
 fn test_analyze() {
@@ -20,13 +22,57 @@ fn test_analyze() {
         witness: None,
         wire_mapping: None,
         aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
     };
 
-    let result = crate::plonk::analyse(circuit).unwrap();
-
+    let result = crate::plonk::analyse(circuit, None).unwrap();
+    assert!(result.check_setup_range().is_ok());
     assert_eq!(CIRCUIT_ANALYZE_RESULT, serde_json::to_string(&result).unwrap());
 }
 
+#[test]
+fn test_estimate_resources() {
+    let calibration = crate::plonk::Calibration {
+        multiexp_secs: 0.01,
+        fft_secs: 0.001,
+        lagrange_secs: 0.1,
+    };
+    let resources = crate::plonk::estimate_resources((1 << 20) - 1, Some(&calibration));
+    assert_eq!((resources.domain_power, resources.srs_power), (20, 20));
+    assert_eq!(resources.srs_monomial_form, "setup_2^20.key");
+    let resources = crate::plonk::estimate_resources(1 << 20, Some(&calibration));
+    assert_eq!(resources.domain_power, 21);
+    assert!(resources.prove_secs.unwrap() > 0.0 && resources.dump_lagrange_secs.unwrap() > 0.0);
+    assert!(resources.prove_memory_bytes > resources.dump_lagrange_memory_bytes);
+    let resources = crate::plonk::estimate_resources(1 << 26, None);
+    assert_eq!(resources.srs_power, 27);
+    assert_eq!((resources.prove_secs, resources.dump_lagrange_secs), (None, None));
+}
+
+#[test]
+fn test_analyze_components() {
+    // symbols of the circuit as if `tmp <== x * x` were a `sq` subcomponent
+    let sym_file = std::env::temp_dir().join("plonkit_test_circuit.sym");
+    fs::write(&sym_file, "1,1,0,main.y\n2,2,1,main.sq.in\n3,3,1,main.sq.out\n4,-1,0,main.unused\n").unwrap();
+    let wire_names = reader::load_symbols(sym_file.to_str().unwrap()).unwrap();
+    assert_eq!(wire_names.len(), 3);
+    let r1cs = reader::load_r1cs(CIRCUIT_FILE);
+    let constraint_components = crate::circom_circuit::constraint_components(&r1cs, &wire_names);
+    assert_eq!(constraint_components, vec![vec!["main", "sq"], vec!["main"]]);
+
+    let circuit = CircomCircuit {
+        r1cs,
+        witness: None,
+        wire_mapping: None,
+        aux_offset: plonk::AUX_OFFSET,
+        constraint_components: Some(constraint_components),
+    };
+    let result = crate::plonk::analyse(circuit, None).unwrap();
+    assert_eq!(result.components.len(), 1);
+    assert_eq!(result.components[0].num_gates, result.num_gates);
+    assert_eq!(crate::transpile::folded_stacks(&result.components), "main 2\nmain;sq 1\n");
+}
+
 #[test]
 fn test_export_verification_key() {
     let circuit = CircomCircuit {
@@ -34,6 +80,7 @@ fn test_export_verification_key() {
         witness: None,
         wire_mapping: None,
         aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
     };
 
     let setup = plonk::SetupForProver::prepare_setup_for_prover(circuit, reader::load_key_monomial_form(MONOMIAL_KEY_FILE), None)
@@ -52,6 +99,7 @@ fn test_prove() {
         witness: Some(reader::load_witness_from_file::<Bn256>(WITNESS_FILE)),
         wire_mapping: None,
         aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
     };
 
     let setup = plonk::SetupForProver::prepare_setup_for_prover(
@@ -72,6 +120,79 @@ fn test_prove() {
     assert_eq!(check_proof, buf);
 }
 
+#[test]
+fn test_optimised_circuit_proof() {
+    let r1cs = reader::load_r1cs(CIRCUIT_FILE);
+    let optimised = crate::optimise::optimise(&r1cs);
+    let circuit_file = std::env::temp_dir().join("plonkit_test_optimised.r1cs.json");
+    let wire_mapping_file = std::env::temp_dir().join("plonkit_test_wire_mapping.json");
+    fs::write(
+        &circuit_file,
+        serde_json::to_string(&crate::optimise::to_json(&optimised.r1cs)).unwrap(),
+    )
+    .unwrap();
+    fs::write(&wire_mapping_file, serde_json::to_string(&optimised.wire_mapping).unwrap()).unwrap();
+
+    // the optimised circuit is proved with the witness of the original one
+    let circuit = CircomCircuit {
+        r1cs: reader::load_r1cs(circuit_file.to_str().unwrap()),
+        witness: Some(reader::load_witness_from_file::<Bn256>(WITNESS_FILE)),
+        wire_mapping: Some(reader::load_wire_mapping(wire_mapping_file.to_str().unwrap())),
+        aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
+    };
+    let setup =
+        plonk::SetupForProver::prepare_setup_for_prover(circuit.clone(), reader::load_key_monomial_form(MONOMIAL_KEY_FILE), None).unwrap();
+    assert!(setup.validate_witness(circuit.clone()).is_ok());
+    let vk = setup.make_verification_key().unwrap();
+    let proof = setup.prove(circuit, DEFAULT_TRANSCRIPT).unwrap();
+    assert!(plonk::verify(&vk, &proof, DEFAULT_TRANSCRIPT).unwrap());
+    // with the same public inputs as the proof of the original circuit
+    assert_eq!(proof.input_values, reader::load_proof::<Bn256>(PROOF_FILE).input_values);
+
+    fs::remove_file(circuit_file).unwrap();
+    fs::remove_file(wire_mapping_file).unwrap();
+}
+
+#[test]
+fn test_bench() {
+    let circuit = CircomCircuit {
+        r1cs: reader::load_r1cs(CIRCUIT_FILE),
+        witness: Some(reader::load_witness_from_file::<Bn256>(WITNESS_FILE)),
+        wire_mapping: None,
+        aux_offset: plonk::AUX_OFFSET,
+        constraint_components: None,
+    };
+    let config = crate::bench::BenchConfig {
+        srs_monomial_form: MONOMIAL_KEY_FILE.to_owned(),
+        srs_lagrange_form: None,
+        srs_forms: vec!["monomial".to_owned(), "lagrange".to_owned()],
+        transcripts: vec![DEFAULT_TRANSCRIPT.to_owned(), "rescue".to_owned()],
+        repetitions: 1,
+    };
+    let result = crate::bench::bench(circuit, &config).unwrap();
+    assert_eq!(result.num_gates, 3);
+    let phases: Vec<&str> = result.phases.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(
+        phases,
+        vec![
+            "transpile",
+            "setup",
+            "vk",
+            "prove_monomial_keccak",
+            "verify_keccak",
+            "prove_monomial_rescue",
+            "verify_rescue",
+            "lagrange_srs",
+            "prove_lagrange_keccak"
+        ]
+    );
+    assert_eq!(result.skipped, vec!["prove_lagrange_rescue"]);
+    if cfg!(target_os = "linux") {
+        assert!(result.phases.iter().all(|p| p.peak_rss_kb.is_some()));
+    }
+}
+
 #[test]
 fn test_verify() {
     let vk = reader::load_verification_key::<Bn256>(VK_FILE);
@@ -79,3 +200,212 @@ fn test_verify() {
     let proof = reader::load_proof::<Bn256>(PROOF_FILE);
     assert!(plonk::verify(&vk, &proof, DEFAULT_TRANSCRIPT).expect("fail to verify proof"));
 }
+
+#[test]
+fn test_check_old_proofs() {
+    let vk = reader::load_verification_key::<Bn256>(VK_FILE);
+    let proof = reader::load_proof::<Bn256>(PROOF_FILE);
+
+    // the test proof is made with the keccak transcript, which the recursion circuit cannot verify
+    let err = crate::recursive::check_old_proofs(&[proof.clone()], &[vk.clone()], &[0], crate::recursive::DEFAULT_VK_TREE_DEPTH)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("proof 0: proof is made with the keccak transcript"));
+    assert!(crate::recursive::check_old_proofs(&[proof], &[vk], &[1], crate::recursive::DEFAULT_VK_TREE_DEPTH).is_err());
+}
+
+#[test]
+fn test_aggregate_from_bytes_errors() {
+    use crate::recursive::{aggregate_from_bytes, AggregationError, DEFAULT_VK_TREE_DEPTH};
+    let proof = fs::read(PROOF_FILE).unwrap();
+    let vk = fs::read(VK_FILE).unwrap();
+    let crs = || reader::load_key_monomial_form(MONOMIAL_KEY_FILE);
+
+    let err = aggregate_from_bytes(crs(), &[&proof, &proof[..10]], &[&vk], &[0, 0], DEFAULT_VK_TREE_DEPTH, None).unwrap_err();
+    assert!(matches!(err, AggregationError::InvalidProof { index: 1, .. }));
+    let err = aggregate_from_bytes(crs(), &[&proof, &proof], &[&vk], &[0, 1], DEFAULT_VK_TREE_DEPTH, None).unwrap_err();
+    assert!(matches!(err, AggregationError::InvalidVkIndex { index: 1, vk_idx: 1 }));
+    // the test proof is made with the keccak transcript
+    let err = aggregate_from_bytes(crs(), &[&proof], &[&vk], &[0], DEFAULT_VK_TREE_DEPTH, None).unwrap_err();
+    assert!(matches!(err, AggregationError::InnerProofs(ref failures) if failures[0].0 == 0));
+}
+
+#[test]
+fn test_generate_rust_verifier() {
+    let vk = reader::load_verification_key::<Bn256>(VK_FILE);
+    let output_dir = std::env::temp_dir().join("plonkit_test_rust_verifier");
+    let output_dir = output_dir.to_str().unwrap();
+    crate::rust_verifier::render_verifier_crate(&vk, "test_verifier", output_dir).unwrap();
+
+    let lib = fs::read_to_string(format!("{}/src/lib.rs", output_dir)).unwrap();
+    assert!(!lib.contains("{{"));
+    assert!(lib.contains(&format!("const DOMAIN_SIZE: usize = {};", vk.n + 1)));
+    assert!(lib.contains(&format!("const NUM_INPUTS: usize = {};", vk.num_inputs)));
+    let manifest = fs::read_to_string(format!("{}/Cargo.toml", output_dir)).unwrap();
+    assert!(manifest.contains("name = \"test_verifier\""));
+    assert!(manifest.contains("edition = \"2021\""));
+    assert!(fs::metadata(format!("{}/src/verifier.rs", output_dir)).is_ok());
+
+    // the emitted verifier accepts the proof, and rejects it once tampered with
+    use crate::rust_verifier::template;
+    let vk = template::VerificationKey::read(&fs::read(VK_FILE).unwrap()).expect("fail to read vk");
+    let mut proof = fs::read(PROOF_FILE).unwrap();
+    assert!(template::verify(&vk, &template::Proof::read(&proof).expect("fail to read proof")));
+    let last = proof.len() - 1;
+    proof[last] ^= 1;
+    assert!(!matches!(template::Proof::read(&proof), Some(proof) if template::verify(&vk, &proof)));
+}
+
+#[cfg(feature = "verifier")]
+#[test]
+fn test_verify_without_prover_stack() {
+    let vk = fs::read(VK_FILE).unwrap();
+    let mut proof = fs::read(PROOF_FILE).unwrap();
+    assert_eq!(crate::verifier::verify_serialized(&vk, &proof), Ok(true));
+
+    // tamper with the last byte of the opening proof at z*omega
+    let last = proof.len() - 1;
+    proof[last] ^= 1;
+    assert!(!crate::verifier::verify_serialized(&vk, &proof).unwrap_or(false));
+}
+
+#[test]
+fn test_load_proofs_and_vk_idxs_from_list() {
+    let list = std::env::temp_dir().join("plonkit_test_proof_list.txt");
+    fs::write(&list, format!("{} 1\n{}\n\n", PROOF_FILE, PROOF_FILE)).unwrap();
+    let (proofs, vk_idxs) = reader::load_proofs_and_vk_idxs_from_list::<Bn256>(list.to_str().unwrap());
+    assert_eq!(proofs.len(), 2);
+    assert_eq!(vk_idxs, vec![1, 0]);
+
+    let list = std::env::temp_dir().join("plonkit_test_vk_list.txt");
+    fs::write(&list, format!("{}\n{}\n", VK_FILE, VK_FILE)).unwrap();
+    let vks = reader::load_verification_keys_from_list::<Bn256>(list.to_str().unwrap());
+    assert_eq!(vks.len(), 2);
+}
+
+#[test]
+fn test_single_verifier_calldata() {
+    let proof = reader::load_proof::<Bn256>(PROOF_FILE);
+    let calldata = crate::calldata::single_verifier_calldata(&proof).unwrap();
+    let args = calldata.args.as_array().unwrap();
+    let num_inputs = args[0].as_array().unwrap().len();
+    let proof_len = args[1].as_array().unwrap().len();
+    assert_eq!(num_inputs, proof.num_inputs);
+    // SERIALIZED_PROOF_LENGTH of the solidity verifier
+    assert_eq!(proof_len, 33);
+    assert_eq!(calldata.data.len(), 4 + 32 * (2 + 1 + num_inputs + 1 + proof_len));
+    assert_eq!(
+        &calldata.data[..4],
+        &crate::calldata::function_selector("verify_serialized_proof(uint256[],uint256[])")
+    );
+}
+
+#[cfg(feature = "evm")]
+#[test]
+fn test_verify_evm() {
+    if std::process::Command::new("solc").arg("--version").output().is_err() {
+        eprintln!("solc is not on PATH, skipping test_verify_evm");
+        return;
+    }
+    let vk = reader::load_verification_key::<Bn256>(VK_FILE);
+    let sol_file = std::env::temp_dir().join("plonkit_test_verifier.sol");
+    let sol_file = sol_file.to_str().unwrap();
+    bellman_vk_codegen::render_verification_key_from_default_template(&vk, sol_file);
+    let bytecode = crate::evm::compile_contract(sol_file, "KeyedVerifier").unwrap();
+
+    let proof = reader::load_proof::<Bn256>(PROOF_FILE);
+    let mut calldata = crate::calldata::single_verifier_calldata(&proof).unwrap().data;
+    let result = crate::evm::deploy_and_call(&bytecode, &calldata).unwrap();
+    assert!(result.valid);
+    assert!(result.gas_used > 21000);
+
+    // tamper with the last word of the serialized proof
+    let last = calldata.len() - 1;
+    calldata[last] ^= 1;
+    assert!(!crate::evm::deploy_and_call(&bytecode, &calldata).unwrap().valid);
+}
+
+#[test]
+fn test_native_circuit() {
+    let mut circuit = reader::load_gate_circuit(NATIVE_CIRCUIT_FILE);
+    circuit.witness = Some(reader::load_witness_from_file::<Bn256>(NATIVE_WITNESS_FILE));
+    let setup =
+        plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, reader::load_key_monomial_form(MONOMIAL_KEY_FILE)).unwrap();
+    let vk = setup.make_verification_key().unwrap();
+    assert_eq!(vk.num_inputs, 1);
+
+    for transcript in &["keccak", "rescue"] {
+        let proof = setup.prove_native(&circuit, transcript).unwrap();
+        assert_eq!(Some(proof.input_values.clone()), circuit.get_public_inputs());
+        assert!(plonk::verify(&vk, &proof, transcript).unwrap());
+    }
+
+    // x^3 + x + 5 != 36
+    use crate::bellman_ce::pairing::{bn256::Fr, ff::PrimeField};
+    let mut witness = circuit.witness.clone().unwrap();
+    witness[0] = Fr::from_str("36").unwrap();
+    circuit.witness = Some(witness);
+    assert!(setup.prove_native(&circuit, DEFAULT_TRANSCRIPT).is_err());
+}
+
+#[test]
+fn test_native_circuit_with_copy_constrained_inputs() {
+    use crate::bellman_ce::pairing::{bn256::Fr, ff::PrimeField};
+    use crate::gate_circuit::{GateCircuit, GateCircuitJson};
+    // two public inputs copy-constrained to each other, and to the square root of a private variable
+    let json: GateCircuitJson = serde_json::from_str(
+        r#"{
+            "num_inputs": 2,
+            "num_variables": 3,
+            "gates": [{ "wires": [1, 1, 2, null], "q_m": "1", "q_c": "-1" }],
+            "copy_constraints": [[0, 1]]
+        }"#,
+    )
+    .unwrap();
+    let mut circuit = GateCircuit::<Bn256>::from_json(json).unwrap();
+    let fr = |s: &str| Fr::from_str(s).unwrap();
+    circuit.witness = Some(vec![fr("3"), fr("3"), fr("9")]);
+    let setup =
+        plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, reader::load_key_monomial_form(MONOMIAL_KEY_FILE)).unwrap();
+    let vk = setup.make_verification_key().unwrap();
+    assert_eq!(vk.num_inputs, 2);
+    let mut proof = setup.prove_native(&circuit, DEFAULT_TRANSCRIPT).unwrap();
+    assert!(plonk::verify(&vk, &proof, DEFAULT_TRANSCRIPT).unwrap());
+
+    // the inputs can't differ, neither in the witness nor in the proof
+    circuit.witness = Some(vec![fr("4"), fr("3"), fr("9")]);
+    assert!(setup.prove_native(&circuit, DEFAULT_TRANSCRIPT).is_err());
+    proof.input_values[0] = fr("4");
+    assert!(!plonk::verify(&vk, &proof, DEFAULT_TRANSCRIPT).unwrap());
+}
+
+#[test]
+fn test_pad_with_dummy_proofs() {
+    use crate::gate_circuit::GateCircuit;
+    use crate::recursive::{check_old_proofs, pad_proofs, prove_dummy, AggregationPadding, DEFAULT_VK_TREE_DEPTH};
+    let circuit = GateCircuit::<Bn256>::dummy(1);
+    let setup =
+        plonk::SetupForProver::prepare_setup_for_native_prover(&circuit, reader::load_key_monomial_form(MONOMIAL_KEY_FILE)).unwrap();
+    let dummy_vk = setup.make_verification_key().unwrap();
+    let crs = reader::load_key_monomial_form(MONOMIAL_KEY_FILE);
+    assert!(prove_dummy(crs, 1, &reader::load_verification_key::<Bn256>(VK_FILE)).is_err());
+
+    // a dummy proof is a valid proof for the recursion circuit
+    let crs = std::sync::Arc::try_unwrap(setup.into_key_monomial_form()).ok().unwrap();
+    let (dummy_proof, crs) = prove_dummy(crs, 1, &dummy_vk).unwrap();
+    assert!(check_old_proofs(&[dummy_proof.clone()], &[dummy_vk.clone()], &[0], DEFAULT_VK_TREE_DEPTH).is_ok());
+    assert!(prove_dummy(crs, 1, &dummy_vk).is_ok());
+
+    // the padding is recorded with the padding proof, so that the real proofs can be padded again
+    let real_proof = reader::load_proof::<Bn256>(PROOF_FILE);
+    let (mut proofs, mut vk_idxs) = (vec![real_proof.clone()], vec![0]);
+    let padding = pad_proofs(&mut proofs, &mut vk_idxs, 3, Some((dummy_proof, 1))).unwrap();
+    assert_eq!((padding.num_real_proofs(), padding.padding_vk_idx), (1, 1));
+    assert_eq!(vk_idxs, vec![0, 1, 1]);
+    let padding: AggregationPadding = serde_json::from_str(&serde_json::to_string(&padding).unwrap()).unwrap();
+    let (mut repadded, mut repadded_vk_idxs) = (vec![real_proof.clone()], vec![0]);
+    padding.pad(&mut repadded, &mut repadded_vk_idxs).unwrap();
+    assert_eq!(repadded_vk_idxs, vk_idxs);
+    assert_eq!(repadded[2].input_values, proofs[2].input_values);
+    assert!(padding.pad(&mut vec![real_proof.clone(), real_proof], &mut vec![0, 0]).is_err());
+}
@@ -14,10 +14,125 @@ pub struct ConstraintStat {
     pub num_gates: usize,
 }
 
+// the shape of an R1CS constraint A * B = C: the number of variables in each linear combination, the constant
+// term left out, and whether A or B has no variable, which makes the constraint linear
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstraintShape {
+    pub linear: bool,
+    pub a: usize,
+    pub b: usize,
+    pub c: usize,
+}
+
+impl ConstraintShape {
+    fn of<E: Engine>(
+        a: &bellman_ce::LinearCombination<E>,
+        b: &bellman_ce::LinearCombination<E>,
+        c: &bellman_ce::LinearCombination<E>,
+    ) -> Self {
+        let num_variables = |lc: &bellman_ce::LinearCombination<E>| {
+            lc.as_ref()
+                .iter()
+                .filter(|(v, _)| v.get_unchecked() != bellman_ce::Index::Input(0))
+                .count()
+        };
+        let (a, b, c) = (num_variables(a), num_variables(b), num_variables(c));
+        Self {
+            linear: a == 0 || b == 0,
+            a,
+            b,
+            c,
+        }
+    }
+
+    // e.g. `multiplication a1 b1 c2`
+    pub fn key(&self) -> String {
+        let kind = if self.linear { "linear" } else { "multiplication" };
+        format!("{} a{} b{} c{}", kind, self.a, self.b, self.c)
+    }
+}
+
+// name of the way the transpiler turned a constraint into gates
+pub fn variant_name(variant: &TranspilationVariant) -> String {
+    #[allow(unreachable_patterns)]
+    match variant {
+        TranspilationVariant::IntoQuadraticGate => "quadratic_gate".to_owned(),
+        TranspilationVariant::IntoLinearGate => "linear_gate".to_owned(),
+        TranspilationVariant::IntoSingleAdditionGate => "single_addition_gate".to_owned(),
+        TranspilationVariant::IntoMultipleAdditionGates => "multiple_addition_gates".to_owned(),
+        TranspilationVariant::MergeLinearCombinations(_, inner) => format!("merge_linear_combinations({})", variant_name(inner)),
+        TranspilationVariant::IntoMultiplicationGate(_) => "multiplication_gate".to_owned(),
+        _ => "other".to_owned(),
+    }
+}
+
+// gates of a circuit component, keyed by the namespaces pushed while synthesizing
+#[derive(serde::Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ComponentStat {
+    pub name: String,
+    // gates of the component, including its subcomponents
+    pub num_gates: usize,
+    // gates of the constraints of the component itself
+    pub num_own_gates: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ComponentStat>,
+}
+
+impl ComponentStat {
+    fn add(&mut self, path: &[String], num_gates: usize) {
+        self.num_gates += num_gates;
+        match path.split_first() {
+            None => self.num_own_gates += num_gates,
+            Some((name, rest)) => {
+                let pos = match self.children.iter().position(|c| &c.name == name) {
+                    Some(pos) => pos,
+                    None => {
+                        self.children.push(ComponentStat {
+                            name: name.clone(),
+                            ..Default::default()
+                        });
+                        self.children.len() - 1
+                    }
+                };
+                self.children[pos].add(rest, num_gates);
+            }
+        }
+    }
+
+    fn fold(&self, prefix: &str, out: &mut String) {
+        let stack = if prefix.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{};{}", prefix, self.name)
+        };
+        if self.num_own_gates > 0 {
+            out.push_str(&format!("{} {}\n", stack, self.num_own_gates));
+        }
+        for child in &self.children {
+            child.fold(&stack, out);
+        }
+    }
+}
+
+// the folded stacks of a component tree, one `main;sub[0];sub 42` line per component with gates of its own,
+// the input format of flamegraph.pl and inferno
+pub fn folded_stacks(components: &[ComponentStat]) -> String {
+    let mut out = String::new();
+    for component in components {
+        component.fold("", &mut out);
+    }
+    out
+}
+
 // a wrapper to interact with bellman_ce lib's plonk transpiler
 pub struct TranspilerWrapper<E: Engine, P: PlonkConstraintSystemParams<E>> {
     inner: Transpiler<E, P>,
     pub constraint_stats: Vec<ConstraintStat>,
+    // the shape of each constraint of constraint_stats
+    pub constraint_shapes: Vec<ConstraintShape>,
+    namespace: Vec<String>,
+    // the root of the component tree, not a component itself
+    components: ComponentStat,
 }
 
 #[allow(clippy::new_without_default)]
@@ -26,8 +141,15 @@ impl<E: Engine, P: PlonkConstraintSystemParams<E>> TranspilerWrapper<E, P> {
         Self {
             inner: Transpiler::<E, P>::new(),
             constraint_stats: Vec::new(),
+            constraint_shapes: Vec::new(),
+            namespace: Vec::new(),
+            components: ComponentStat::default(),
         }
     }
+    // the top level components, empty if no namespace was pushed
+    pub fn components(&self) -> &[ComponentStat] {
+        &self.components.children
+    }
     pub fn into_hints_and_num_gates(self) -> (usize, Vec<(usize, TranspilationVariant)>) {
         self.inner.into_hints_and_num_gates()
     }
@@ -99,11 +221,16 @@ impl<E: Engine, P: PlonkConstraintSystemParams<E>> bellman_ce::ConstraintSystem<
     {
         let num_gates_before = self.inner.num_gates();
         let name_ = ann().into();
-        self.inner.enforce(|| name_.clone(), a, b, c);
-        self.constraint_stats.push(ConstraintStat {
-            name: name_,
-            num_gates: self.inner.num_gates() - num_gates_before,
-        });
+        let (a, b, c) = (
+            a(bellman_ce::LinearCombination::zero()),
+            b(bellman_ce::LinearCombination::zero()),
+            c(bellman_ce::LinearCombination::zero()),
+        );
+        self.constraint_shapes.push(ConstraintShape::of(&a, &b, &c));
+        self.inner.enforce(|| name_.clone(), |_| a, |_| b, |_| c);
+        let num_gates = self.inner.num_gates() - num_gates_before;
+        self.components.add(&self.namespace, num_gates);
+        self.constraint_stats.push(ConstraintStat { name: name_, num_gates });
     }
 
     fn push_namespace<NR, N>(&mut self, n: N)
@@ -111,10 +238,13 @@ impl<E: Engine, P: PlonkConstraintSystemParams<E>> bellman_ce::ConstraintSystem<
         NR: Into<String>,
         N: FnOnce() -> NR,
     {
-        self.inner.push_namespace(n)
+        let name: String = n().into();
+        self.namespace.push(name.clone());
+        self.inner.push_namespace(|| name)
     }
 
     fn pop_namespace(&mut self) {
+        self.namespace.pop();
         self.inner.pop_namespace()
     }
 
@@ -137,3 +267,29 @@ pub fn transpile_with_gates_count<E: Engine, C: bellman_ce::Circuit<E>>(
 
     Ok((n, hints))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> Vec<String> {
+        path.split('.').map(|s| s.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_component_tree() {
+        let mut root = ComponentStat::default();
+        root.add(&path("main"), 1);
+        root.add(&path("main.hash[0]"), 3);
+        root.add(&path("main.hash[0].sbox"), 2);
+        root.add(&path("main.hash[1]"), 3);
+        let main = &root.children[0];
+        assert_eq!((main.num_gates, main.num_own_gates), (9, 1));
+        assert_eq!(main.children.len(), 2);
+        assert_eq!((main.children[0].num_gates, main.children[0].num_own_gates), (5, 3));
+        assert_eq!(
+            folded_stacks(&root.children),
+            "main 1\nmain;hash[0] 3\nmain;hash[0];sbox 2\nmain;hash[1] 3\n"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::bellman_ce::pairing::{ff::PrimeField, Engine};
+use anyhow::{bail, format_err};
 use franklin_crypto::plonk::circuit::bigint::field::RnsParameters;
 pub use num_bigint::BigUint;
 use num_traits::Num;
@@ -12,6 +13,26 @@ pub fn repr_to_big<T: Display>(r: T) -> String {
     BigUint::from_str_radix(&format!("{}", r)[2..], 16).unwrap().to_str_radix(10)
 }
 
+/// encode bytes as a "0x" prefixed hex string
+pub fn encode_hex(bytes: &[u8]) -> String {
+    format!("0x{}", bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+}
+
+/// decode a hex string, with or without the "0x" prefix
+pub fn decode_hex(s: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let s = s.trim();
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() != 2 {
+                bail!("hex string has an odd length");
+            }
+            u8::from_str_radix(std::str::from_utf8(pair)?, 16).map_err(|e| format_err!("invalid hex string: {}", e))
+        })
+        .collect()
+}
+
 fn from_single_size_limb_witnesses<E: Engine, F: PrimeField>(witnesses: &[BigUint], params: &RnsParameters<E, F>) -> F {
     assert_eq!(params.num_limbs_for_in_field_representation, witnesses.len());
     assert!(
@@ -131,6 +152,16 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_hex() {
+        assert_eq!(decode_hex("0x01ff\n").unwrap(), vec![1, 255]);
+        assert_eq!(decode_hex("ab").unwrap(), vec![0xab]);
+        assert!(decode_hex("0x1").is_err());
+        assert!(decode_hex("zz").is_err());
+        assert_eq!(encode_hex(&[1, 255]), "0x01ff");
+        assert_eq!(decode_hex(&encode_hex(&[0, 16, 171])).unwrap(), vec![0, 16, 171]);
+    }
+
     #[test]
     fn test_witness_to_field() {
         type Fq = <Bn256 as Engine>::Fq;
@@ -0,0 +1,578 @@
+// A standalone port of `better_cs::verifier::verify` for `PlonkCsWidth4WithNextStepParams` proofs with
+// the rolling keccak transcript. It only depends on a pairing library, and stays `no_std` + `alloc` friendly.
+// The same source is also emitted into the crates rendered by `rust_verifier`.
+use alloc::vec::Vec;
+use ark_bn254::{Bn254, Fq, Fq2, Fr, G1Affine, G1Projective, G2Affine};
+use ark_ec::{AffineCurve, PairingEngine, ProjectiveCurve};
+use ark_ff::{BigInteger, BigInteger256, FftField, Field, One, PrimeField, Zero};
+use tiny_keccak::{Hasher, Keccak};
+
+pub const STATE_WIDTH: usize = 4;
+const SELECTOR_Q_M_INDEX: usize = STATE_WIDTH;
+const SELECTOR_Q_CONST_INDEX: usize = STATE_WIDTH + 1;
+
+// verification key of a width-4 circuit with access to the next step
+pub struct VerificationKey {
+    pub n: usize,
+    pub num_inputs: usize,
+    pub omega: Fr,
+    pub selector_commitments: [G1Affine; STATE_WIDTH + 2],
+    pub next_step_selector_commitments: [G1Affine; 1],
+    pub permutation_commitments: [G1Affine; STATE_WIDTH],
+    pub non_residues: [Fr; STATE_WIDTH - 1],
+    pub g2_elements: [G2Affine; 2],
+}
+
+// a plonk proof, in the same layout as `better_cs::keys::Proof`
+pub struct Proof {
+    pub num_inputs: usize,
+    pub n: usize,
+    pub input_values: Vec<Fr>,
+    pub wire_commitments: [G1Affine; STATE_WIDTH],
+    pub grand_product_commitment: G1Affine,
+    pub quotient_poly_commitments: [G1Affine; STATE_WIDTH],
+    pub wire_values_at_z: [Fr; STATE_WIDTH],
+    pub wire_values_at_z_omega: [Fr; 1],
+    pub grand_product_at_z_omega: Fr,
+    pub quotient_polynomial_at_z: Fr,
+    pub linearization_polynomial_at_z: Fr,
+    pub permutation_polynomials_at_z: [Fr; STATE_WIDTH - 1],
+    pub opening_at_z_proof: G1Affine,
+    pub opening_at_z_omega_proof: G1Affine,
+}
+
+// a cursor over a serialized proof, following `better_cs::keys::Proof::write`
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Some(u64::from_be_bytes(buf))
+    }
+
+    fn read_len(&mut self, expected: usize) -> Option<()> {
+        if self.read_u64()? as usize == expected {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn read_fr(&mut self) -> Option<Fr> {
+        Fr::from_repr(read_bigint(self.take(32)?))
+    }
+
+    fn read_g1(&mut self) -> Option<G1Affine> {
+        let encoded = self.take(64)?;
+        // the two top bits of the first byte are the compression and infinity flags
+        if encoded[0] & (1 << 7) != 0 {
+            return None;
+        }
+        if encoded[0] & (1 << 6) != 0 {
+            let rest_is_zero = encoded[0] == (1 << 6) && encoded[1..].iter().all(|b| *b == 0);
+            return if rest_is_zero { Some(G1Affine::zero()) } else { None };
+        }
+        let x = Fq::from_repr(read_bigint(&encoded[..32]))?;
+        let y = Fq::from_repr(read_bigint(&encoded[32..]))?;
+        new_g1(x, y)
+    }
+
+    fn read_g2(&mut self) -> Option<G2Affine> {
+        let encoded = self.take(128)?;
+        if encoded[0] & (1 << 7) != 0 {
+            return None;
+        }
+        if encoded[0] & (1 << 6) != 0 {
+            let rest_is_zero = encoded[0] == (1 << 6) && encoded[1..].iter().all(|b| *b == 0);
+            return if rest_is_zero { Some(G2Affine::zero()) } else { None };
+        }
+        // coordinates are written as x.c1, x.c0, y.c1, y.c0
+        let fq = |i: usize| Fq::from_repr(read_bigint(&encoded[(32 * i)..(32 * (i + 1))]));
+        new_g2(Fq2::new(fq(1)?, fq(0)?), Fq2::new(fq(3)?, fq(2)?))
+    }
+
+    fn read_fr_array<const N: usize>(&mut self) -> Option<[Fr; N]> {
+        self.read_len(N)?;
+        let mut result = [Fr::zero(); N];
+        for el in result.iter_mut() {
+            *el = self.read_fr()?;
+        }
+        Some(result)
+    }
+
+    fn read_g1_array<const N: usize>(&mut self) -> Option<[G1Affine; N]> {
+        self.read_len(N)?;
+        let mut result = [G1Affine::zero(); N];
+        for el in result.iter_mut() {
+            *el = self.read_g1()?;
+        }
+        Some(result)
+    }
+}
+
+impl VerificationKey {
+    // deserialize a verification key written by `plonkit export-verification-key` (vk.bin)
+    pub fn read(bytes: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader { bytes };
+        let n = reader.read_u64()? as usize;
+        let num_inputs = reader.read_u64()? as usize;
+        let domain_size = n.checked_add(1)?;
+        if !domain_size.is_power_of_two() {
+            return None;
+        }
+        let vk = VerificationKey {
+            n,
+            num_inputs,
+            omega: Fr::get_root_of_unity(domain_size)?,
+            selector_commitments: reader.read_g1_array()?,
+            next_step_selector_commitments: reader.read_g1_array()?,
+            permutation_commitments: reader.read_g1_array()?,
+            non_residues: reader.read_fr_array()?,
+            g2_elements: [reader.read_g2()?, reader.read_g2()?],
+        };
+        if !reader.bytes.is_empty() {
+            return None;
+        }
+        Some(vk)
+    }
+}
+
+impl Proof {
+    // deserialize a proof written by `plonkit prove` (proof.bin)
+    pub fn read(bytes: &[u8]) -> Option<Self> {
+        let mut reader = ByteReader { bytes };
+        let n = reader.read_u64()? as usize;
+        let num_inputs = reader.read_u64()? as usize;
+        let mut input_values = Vec::with_capacity(num_inputs);
+        for _ in 0..num_inputs {
+            input_values.push(reader.read_fr()?);
+        }
+        let proof = Proof {
+            num_inputs,
+            n,
+            input_values,
+            wire_commitments: reader.read_g1_array()?,
+            grand_product_commitment: reader.read_g1()?,
+            quotient_poly_commitments: reader.read_g1_array()?,
+            wire_values_at_z: reader.read_fr_array()?,
+            wire_values_at_z_omega: reader.read_fr_array()?,
+            grand_product_at_z_omega: reader.read_fr()?,
+            quotient_polynomial_at_z: reader.read_fr()?,
+            linearization_polynomial_at_z: reader.read_fr()?,
+            permutation_polynomials_at_z: reader.read_fr_array()?,
+            opening_at_z_proof: reader.read_g1()?,
+            opening_at_z_omega_proof: reader.read_g1()?,
+        };
+        if !reader.bytes.is_empty() {
+            return None;
+        }
+        Some(proof)
+    }
+}
+
+// build a G1 point from its coordinates. (0, 0) stands for the point at infinity
+pub fn new_g1(x: Fq, y: Fq) -> Option<G1Affine> {
+    if x.is_zero() && y.is_zero() {
+        return Some(G1Affine::zero());
+    }
+    let point = G1Affine::new(x, y, false);
+    if point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve() {
+        Some(point)
+    } else {
+        None
+    }
+}
+
+// build a G2 point from its coordinates
+pub fn new_g2(x: Fq2, y: Fq2) -> Option<G2Affine> {
+    let point = G2Affine::new(x, y, false);
+    if point.is_on_curve() && point.is_in_correct_subgroup_assuming_on_curve() {
+        Some(point)
+    } else {
+        None
+    }
+}
+
+// read a 32 bytes big-endian integer
+fn read_bigint(bytes: &[u8]) -> BigInteger256 {
+    let mut limbs = [0u64; 4];
+    for (i, limb) in limbs.iter_mut().enumerate() {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&bytes[(24 - 8 * i)..(32 - 8 * i)]);
+        *limb = u64::from_be_bytes(buf);
+    }
+    BigInteger256::new(limbs)
+}
+
+// parse a "0x..." big-endian hex representation
+pub fn bigint_from_hex(s: &str) -> Option<BigInteger256> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if s.len() != 64 {
+        return None;
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(read_bigint(&bytes))
+}
+
+pub fn fr_from_hex(s: &str) -> Option<Fr> {
+    Fr::from_repr(bigint_from_hex(s)?)
+}
+
+pub fn fq_from_hex(s: &str) -> Option<Fq> {
+    Fq::from_repr(bigint_from_hex(s)?)
+}
+
+fn keccak256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    for part in parts {
+        hasher.update(part);
+    }
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+// the same construction as bellman's RollingKeccakTranscript
+struct Transcript {
+    state_0: [u8; 32],
+    state_1: [u8; 32],
+    challenge_counter: u32,
+}
+
+impl Transcript {
+    const DST_0_TAG: u32 = 0;
+    const DST_1_TAG: u32 = 1;
+    const CHALLENGE_DST_TAG: u32 = 2;
+
+    fn new() -> Self {
+        Self {
+            state_0: [0u8; 32],
+            state_1: [0u8; 32],
+            challenge_counter: 0,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        let old_state_0 = self.state_0;
+        self.state_0 = keccak256(&[&Self::DST_0_TAG.to_be_bytes(), &old_state_0, &self.state_1, bytes]);
+        self.state_1 = keccak256(&[&Self::DST_1_TAG.to_be_bytes(), &old_state_0, &self.state_1, bytes]);
+    }
+
+    fn commit_field_element<F: PrimeField>(&mut self, el: &F) {
+        self.update(&el.into_repr().to_bytes_be());
+    }
+
+    fn commit_point(&mut self, point: &G1Affine) {
+        if point.is_zero() {
+            self.commit_field_element(&Fq::zero());
+            self.commit_field_element(&Fq::zero());
+        } else {
+            self.commit_field_element(&point.x);
+            self.commit_field_element(&point.y);
+        }
+    }
+
+    fn get_challenge(&mut self) -> Fr {
+        let value = keccak256(&[
+            &Self::CHALLENGE_DST_TAG.to_be_bytes(),
+            &self.state_0,
+            &self.state_1,
+            &self.challenge_counter.to_be_bytes(),
+        ]);
+        self.challenge_counter += 1;
+        // shave the top bits so that the value always fits into the field
+        let mut repr = read_bigint(&value);
+        repr.0[3] &= u64::MAX >> 3;
+        Fr::from_repr(repr).expect("in a field")
+    }
+}
+
+fn evaluate_vanishing_for_size(at: &Fr, domain_size: u64) -> Fr {
+    let mut result = at.pow([domain_size]);
+    result -= Fr::one();
+    result
+}
+
+fn evaluate_lagrange_poly_at_point(poly_number: usize, domain_size: u64, omega: &Fr, at: &Fr) -> Option<Fr> {
+    let omega_power = omega.pow([poly_number as u64]);
+    let mut num = evaluate_vanishing_for_size(at, domain_size);
+    num *= &omega_power;
+    let mut den = *at;
+    den -= &omega_power;
+    den *= &Fr::from(domain_size);
+    Some(num * den.inverse()?)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifierError {
+    InvalidVerificationKey,
+    InvalidProof,
+}
+
+impl core::fmt::Display for VerifierError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            VerifierError::InvalidVerificationKey => write!(f, "invalid verification key"),
+            VerifierError::InvalidProof => write!(f, "invalid proof"),
+        }
+    }
+}
+
+// verify a serialized plonk proof (proof.bin) against a serialized verification key (vk.bin)
+pub fn verify_serialized(vk_bytes: &[u8], proof_bytes: &[u8]) -> Result<bool, VerifierError> {
+    let vk = VerificationKey::read(vk_bytes).ok_or(VerifierError::InvalidVerificationKey)?;
+    let proof = Proof::read(proof_bytes).ok_or(VerifierError::InvalidProof)?;
+    Ok(verify(&vk, &proof))
+}
+
+// verify a plonk proof against a verification key
+pub fn verify(vk: &VerificationKey, proof: &Proof) -> bool {
+    verify_inner(vk, proof).unwrap_or(false)
+}
+
+fn verify_inner(vk: &VerificationKey, proof: &Proof) -> Option<bool> {
+    if proof.n != vk.n || proof.num_inputs != vk.num_inputs || proof.input_values.len() != vk.num_inputs {
+        return None;
+    }
+    let required_domain_size = (proof.n + 1) as u64;
+    if !required_domain_size.is_power_of_two() {
+        return None;
+    }
+
+    let mut transcript = Transcript::new();
+    for inp in proof.input_values.iter() {
+        transcript.commit_field_element(inp);
+    }
+    for w in proof.wire_commitments.iter() {
+        transcript.commit_point(w);
+    }
+    let beta = transcript.get_challenge();
+    let gamma = transcript.get_challenge();
+
+    transcript.commit_point(&proof.grand_product_commitment);
+    let alpha = transcript.get_challenge();
+
+    for w in proof.quotient_poly_commitments.iter() {
+        transcript.commit_point(w);
+    }
+    let z = transcript.get_challenge();
+    let mut z_by_omega = z;
+    z_by_omega *= &vk.omega;
+
+    for el in proof.wire_values_at_z.iter() {
+        transcript.commit_field_element(el);
+    }
+    for el in proof.wire_values_at_z_omega.iter() {
+        transcript.commit_field_element(el);
+    }
+    for el in proof.permutation_polynomials_at_z.iter() {
+        transcript.commit_field_element(el);
+    }
+    transcript.commit_field_element(&proof.quotient_polynomial_at_z);
+    transcript.commit_field_element(&proof.linearization_polynomial_at_z);
+    transcript.commit_field_element(&proof.grand_product_at_z_omega);
+
+    let l_0_at_z = evaluate_lagrange_poly_at_point(0, required_domain_size, &vk.omega, &z)?;
+
+    // check the relationship at z
+    {
+        let mut lhs = proof.quotient_polynomial_at_z;
+        lhs *= &evaluate_vanishing_for_size(&z, required_domain_size);
+
+        let mut rhs = proof.linearization_polynomial_at_z;
+        for (idx, input) in proof.input_values.iter().enumerate() {
+            let mut tmp = evaluate_lagrange_poly_at_point(idx, required_domain_size, &vk.omega, &z)?;
+            tmp *= input;
+            rhs += &tmp;
+        }
+
+        let mut quotient_linearization_challenge = alpha;
+
+        let mut z_part = proof.grand_product_at_z_omega;
+        for (w, p) in proof.wire_values_at_z.iter().zip(proof.permutation_polynomials_at_z.iter()) {
+            let mut tmp = *p;
+            tmp *= &beta;
+            tmp += &gamma;
+            tmp += w;
+            z_part *= &tmp;
+        }
+        let mut tmp = gamma;
+        tmp += &proof.wire_values_at_z[STATE_WIDTH - 1];
+        z_part *= &tmp;
+        z_part *= &quotient_linearization_challenge;
+        rhs -= &z_part;
+
+        quotient_linearization_challenge *= &alpha;
+        let mut tmp = l_0_at_z;
+        tmp *= &quotient_linearization_challenge;
+        rhs -= &tmp;
+
+        if lhs != rhs {
+            return Some(false);
+        }
+    }
+
+    let v = transcript.get_challenge();
+    transcript.commit_point(&proof.opening_at_z_proof);
+    transcript.commit_point(&proof.opening_at_z_omega_proof);
+    let u = transcript.get_challenge();
+
+    let z_in_domain_size = z.pow([required_domain_size]);
+
+    // r(X) + witness + all permutations + 1
+    let v_power_for_standalone_z_x_opening = (1 + 1 + STATE_WIDTH + (STATE_WIDTH - 1)) as u64;
+
+    let virtual_commitment_for_linearization_poly = {
+        let mut r = G1Projective::zero();
+
+        r.add_assign_mixed(&vk.selector_commitments[SELECTOR_Q_CONST_INDEX]);
+        for i in 0..STATE_WIDTH {
+            r += &vk.selector_commitments[i].mul(proof.wire_values_at_z[i].into_repr());
+        }
+        let mut scalar = proof.wire_values_at_z[0];
+        scalar *= &proof.wire_values_at_z[1];
+        r += &vk.selector_commitments[SELECTOR_Q_M_INDEX].mul(scalar.into_repr());
+        r += &vk.next_step_selector_commitments[0].mul(proof.wire_values_at_z_omega[0].into_repr());
+
+        let grand_product_part_at_z = {
+            let mut scalar = Fr::one();
+            let one = Fr::one();
+            for (wire, non_res) in proof
+                .wire_values_at_z
+                .iter()
+                .zip(Some(&one).into_iter().chain(vk.non_residues.iter()))
+            {
+                let mut tmp = z;
+                tmp *= non_res;
+                tmp *= &beta;
+                tmp += wire;
+                tmp += &gamma;
+                scalar *= &tmp;
+            }
+            scalar *= &alpha;
+
+            let mut tmp = l_0_at_z;
+            tmp *= &alpha;
+            tmp *= &alpha;
+            scalar += &tmp;
+            scalar
+        };
+
+        let grand_product_part_at_z_omega = {
+            let mut tmp = v.pow([v_power_for_standalone_z_x_opening]);
+            tmp *= &u;
+            tmp
+        };
+
+        let last_permutation_part_at_z = {
+            let mut scalar = Fr::one();
+            for (wire, perm_at_z) in proof.wire_values_at_z.iter().zip(proof.permutation_polynomials_at_z.iter()) {
+                let mut tmp = beta;
+                tmp *= perm_at_z;
+                tmp += wire;
+                tmp += &gamma;
+                scalar *= &tmp;
+            }
+            scalar *= &beta;
+            scalar *= &proof.grand_product_at_z_omega;
+            scalar *= &alpha;
+            scalar
+        };
+
+        let mut tmp = proof.grand_product_commitment.mul(grand_product_part_at_z.into_repr());
+        tmp -= &vk.permutation_commitments[STATE_WIDTH - 1].mul(last_permutation_part_at_z.into_repr());
+        r += &tmp;
+
+        r = r.mul(v.into_repr());
+        r += &proof.grand_product_commitment.mul(grand_product_part_at_z_omega.into_repr());
+        r
+    };
+
+    // reconstruct the commitment for the quotient polynomial
+    let mut commitments_aggregation = proof.quotient_poly_commitments[0].into_projective();
+    let mut current = z_in_domain_size;
+    for part in proof.quotient_poly_commitments.iter().skip(1) {
+        commitments_aggregation += &part.mul(current.into_repr());
+        current *= &z_in_domain_size;
+    }
+
+    let mut multiopening_challenge = v;
+    commitments_aggregation += &virtual_commitment_for_linearization_poly;
+
+    for com in proof.wire_commitments.iter() {
+        multiopening_challenge *= &v;
+        commitments_aggregation += &com.mul(multiopening_challenge.into_repr());
+    }
+    for com in vk.permutation_commitments[..(STATE_WIDTH - 1)].iter() {
+        multiopening_challenge *= &v;
+        commitments_aggregation += &com.mul(multiopening_challenge.into_repr());
+    }
+    // skip z(X) at z
+    multiopening_challenge *= &v;
+
+    // the last wire commitment is opened at z*omega
+    multiopening_challenge *= &v;
+    let mut scalar = multiopening_challenge;
+    scalar *= &u;
+    commitments_aggregation += &proof.wire_commitments[STATE_WIDTH - 1].mul(scalar.into_repr());
+
+    // subtract the opening values using one multiplication
+    let mut multiopening_challenge_for_values = Fr::one();
+    let mut aggregated_value = proof.quotient_polynomial_at_z;
+    for value_at_z in Some(&proof.linearization_polynomial_at_z)
+        .into_iter()
+        .chain(proof.wire_values_at_z.iter())
+        .chain(proof.permutation_polynomials_at_z.iter())
+    {
+        multiopening_challenge_for_values *= &v;
+        let mut tmp = *value_at_z;
+        tmp *= &multiopening_challenge_for_values;
+        aggregated_value += &tmp;
+    }
+    for value_at_z_omega in [proof.grand_product_at_z_omega, proof.wire_values_at_z_omega[0]].iter() {
+        multiopening_challenge_for_values *= &v;
+        let mut scalar = multiopening_challenge_for_values;
+        scalar *= &u;
+        let mut tmp = *value_at_z_omega;
+        tmp *= &scalar;
+        aggregated_value += &tmp;
+    }
+    if multiopening_challenge != multiopening_challenge_for_values {
+        return None;
+    }
+
+    commitments_aggregation -= &G1Affine::prime_subgroup_generator().mul(aggregated_value.into_repr());
+
+    let mut pair_with_generator = commitments_aggregation;
+    pair_with_generator += &proof.opening_at_z_proof.mul(z.into_repr());
+    let mut scalar = z_by_omega;
+    scalar *= &u;
+    pair_with_generator += &proof.opening_at_z_omega_proof.mul(scalar.into_repr());
+
+    let mut pair_with_x = proof.opening_at_z_omega_proof.mul(u.into_repr());
+    pair_with_x.add_assign_mixed(&proof.opening_at_z_proof);
+    let pair_with_x = -pair_with_x;
+
+    let valid = Bn254::product_of_pairings(&[
+        (pair_with_generator.into_affine().into(), vk.g2_elements[0].into()),
+        (pair_with_x.into_affine().into(), vk.g2_elements[1].into()),
+    ])
+    .is_one();
+
+    Some(valid)
+}
@@ -0,0 +1,37 @@
+// runs the plonkit binary, to check its exit codes and `--json` output
+use std::process::Command;
+
+const VK_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/simple/vk.bin");
+const PROOF_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test/circuits/simple/proof.bin");
+
+#[test]
+fn test_verify_invalid_proof() {
+    // the test proof is made with the keccak transcript, so it doesn't verify with rescue
+    let output = Command::new(env!("CARGO_BIN_EXE_plonkit"))
+        .args(["verify", "--json", "-p", PROOF_FILE, "-v", VK_FILE, "-t", "rescue"])
+        .env("PLONKIT_MANIFEST", "no_such_manifest.toml")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(result["command"], "verify");
+    assert_eq!(result["ok"], false);
+    assert_eq!(result["exit_code"], 3);
+    assert_eq!(result["results"]["valid"], false);
+    assert_eq!(result["error"], "Proof is invalid!");
+}
+
+#[test]
+fn test_queue_submit_json_of_the_job() {
+    // the `--json` flag after `--` belongs to the queued job, not to `queue submit`
+    let queue = std::env::temp_dir().join("plonkit_test_cli_queue");
+    let _ = std::fs::remove_dir_all(&queue);
+    let output = Command::new(env!("CARGO_BIN_EXE_plonkit"))
+        .args(["queue", "-q", queue.to_str().unwrap(), "submit", "--", "prove", "--json"])
+        .env("PLONKIT_MANIFEST", "no_such_manifest.toml")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}